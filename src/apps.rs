@@ -0,0 +1,144 @@
+use sha1::{Digest, Sha1};
+use std::path::PathBuf;
+
+use crate::error::RequestError;
+use crate::image::Image;
+
+/// Minimum similarity for a fuzzy app name match
+const FUZZY_THRESHOLD: f64 = 0.8;
+
+/// Installed app as reported by query-apps
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct App {
+    pub id:         String,
+    pub name:       String,
+    pub app_type:   String,
+    pub subtype:    String,
+    pub version:    String,
+}
+
+impl App {
+    /// Parse the query-apps XML into a list of apps
+    pub fn parse_list(xml: &str) -> Result<Vec<App>, RequestError> {
+        let document = roxmltree::Document::parse(xml).map_err(|e| RequestError::Content(e.to_string()))?;
+
+        Ok(document.descendants()
+            .filter(|node| node.has_tag_name("app"))
+            .map(|node| App {
+                id: String::from(node.attribute("id").unwrap_or_default()),
+                name: String::from(node.text().unwrap_or_default().trim()),
+                app_type: String::from(node.attribute("type").unwrap_or_default()),
+                subtype: String::from(node.attribute("subtype").unwrap_or_default()),
+                version: String::from(node.attribute("version").unwrap_or_default()),
+            })
+            .collect())
+    }
+
+    /// Numeric channel id, if this app can be launched by id
+    pub fn channel_id(&self) -> Option<i32> {
+        self.id.parse::<i32>().ok()
+    }
+
+    /// Find an app by name: case-insensitively, then ignoring spaces and punctuation,
+    /// then by substring, and finally by fuzzy similarity
+    pub fn find_by_name<'a>(apps: &'a [App], name: &str) -> Result<&'a App, RequestError> {
+        let wanted = normalize(name);
+        if wanted.is_empty() {
+            return Err(RequestError::UnknownApp { name: String::from(name) });
+        }
+
+        let lowercase = name.trim().to_lowercase();
+        let exact: Vec<&App> = apps.iter().filter(|app| app.name.to_lowercase() == lowercase).collect();
+        if !exact.is_empty() {
+            return pick(name, exact);
+        }
+
+        let normalized: Vec<&App> = apps.iter().filter(|app| normalize(&app.name) == wanted).collect();
+        if !normalized.is_empty() {
+            return pick(name, normalized);
+        }
+
+        let partial: Vec<&App> = apps.iter().filter(|app| normalize(&app.name).contains(&wanted)).collect();
+        if !partial.is_empty() {
+            return pick(name, partial);
+        }
+
+        let scored: Vec<(&App, f64)> = apps.iter()
+            .map(|app| (app, strsim::jaro_winkler(&normalize(&app.name), &wanted)))
+            .filter(|(_, score)| *score >= FUZZY_THRESHOLD)
+            .collect();
+        let best = scored.iter().map(|(_, score)| *score).fold(0.0, f64::max);
+        let fuzzy: Vec<&App> = scored.into_iter()
+            .filter(|(_, score)| best - score < 0.02)
+            .map(|(app, _)| app)
+            .collect();
+        pick(name, fuzzy)
+    }
+}
+
+/// Lowercase a name and drop everything but letters and digits
+fn normalize(name: &str) -> String {
+    name.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Return the only match, or an error listing the candidates
+fn pick<'a>(name: &str, matches: Vec<&'a App>) -> Result<&'a App, RequestError> {
+    match matches.len() {
+        0 => Err(RequestError::UnknownApp { name: String::from(name) }),
+        1 => Ok(matches[0]),
+        _ => Err(RequestError::AmbiguousApp {
+            name: String::from(name),
+            candidates: matches.iter().map(|app| app.name.clone()).collect(),
+        }),
+    }
+}
+
+/// Result of a verified app install
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstallReport {
+    pub app:                App,
+    pub previous_version:   Option<String>,
+}
+
+impl InstallReport {
+    /// Whether the app was newly installed rather than updated
+    pub fn is_new(&self) -> bool {
+        self.previous_version.is_none()
+    }
+
+    /// Whether the app was already installed at the version it still has
+    pub fn is_unchanged(&self) -> bool {
+        self.previous_version.as_deref() == Some(self.app.version.as_str())
+    }
+}
+
+/// On-disk icon cache, addressed by channel id and app version
+#[derive(Clone, Debug)]
+pub struct IconCache {
+    pub dir: PathBuf,
+}
+
+impl IconCache {
+    /// Use the given directory for cached icons
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Path of the cache entry for a channel id and app version
+    pub fn path(&self, channel_id: i32, version: &str) -> PathBuf {
+        let hash = Sha1::digest(format!("{}@{}", channel_id, version));
+        self.dir.join(hex::encode(hash))
+    }
+
+    /// Look up a cached icon
+    pub fn get(&self, channel_id: i32, version: &str) -> Option<Image> {
+        let bytes = std::fs::read(self.path(channel_id, version)).ok()?;
+        Image::from_bytes(bytes)
+    }
+
+    /// Store an icon in the cache
+    pub fn put(&self, channel_id: i32, version: &str, icon: &Image) -> Result<(), RequestError> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| RequestError::Io(e.to_string()))?;
+        icon.save(self.path(channel_id, version))
+    }
+}
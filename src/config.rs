@@ -1,12 +1,153 @@
-use config::{Config, File};
+use config::{Config, File, FileFormat};
+use serde::Deserialize;
 use std::collections::HashMap;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
 
-#[allow(dead_code)]
-pub fn load_from_file(path: &str) -> HashMap<String, String> {
-    let config = Config::builder()
-        .add_source(File::with_name(path))
-        .build()
-        .unwrap();
+use crate::key::{ConfigKey, EnvKey, FileKey, KeyEncoding, KeyError, KeyProvider, SecretKey};
 
-    config.try_deserialize::<HashMap<String, String>>().unwrap()
-}
\ No newline at end of file
+/// Default device profiles file, resolved by the config crate with any supported extension
+pub const DEFAULT_PATH: &str = "conf/devices";
+
+/// Environment variable which overrides the default device profiles file
+pub const PATH_VAR: &str = "ECP_CONFIG";
+
+/// Device profiles loaded from a TOML, YAML or JSON file
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub devices: HashMap<String, DeviceProfile>,
+}
+
+/// Connection settings for a single named device
+#[derive(Clone, Debug, Deserialize)]
+pub struct DeviceProfile {
+    pub address:    String,
+    #[serde(default = "default_port")]
+    pub port:       usize,
+    pub key:        KeySource,
+    #[serde(default = "default_origin")]
+    pub origin:     String,
+    #[serde(default)]
+    pub timeouts:   Timeouts,
+    #[serde(default)]
+    pub groups:     Vec<String>,
+}
+
+/// Optional timeouts in milliseconds
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+pub struct Timeouts {
+    pub connect_ms: Option<u64>,
+    pub request_ms: Option<u64>,
+}
+
+/// Where a device profile gets its auth key
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum KeySource {
+    Literal { value: SecretKey },
+    Env { var: String, #[serde(default = "default_encoding")] encoding: KeyEncoding },
+    File { path: PathBuf, #[serde(default = "default_encoding")] encoding: KeyEncoding },
+    Config { path: String, name: String, #[serde(default = "default_encoding")] encoding: KeyEncoding },
+}
+
+/// Failure to load or use a device profile
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ProfileError {
+    Config(String),
+    UnknownDevice(String),
+    InvalidAddress(String),
+    Key(KeyError),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::Config(e) => write!(f, "unable to load device profiles: {}", e),
+            ProfileError::UnknownDevice(name) => write!(f, "no device profile named {}", name),
+            ProfileError::InvalidAddress(address) => write!(f, "invalid IPv4 address: {}", address),
+            ProfileError::Key(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+impl From<KeyError> for ProfileError {
+    fn from(e: KeyError) -> Self {
+        ProfileError::Key(e)
+    }
+}
+
+fn default_port() -> usize { 8060 }
+
+fn default_origin() -> String { String::from("Android") }
+
+fn default_encoding() -> KeyEncoding { KeyEncoding::Raw }
+
+impl Settings {
+    /// Load device profiles from the file named by ECP_CONFIG, or the default path
+    pub fn load_default() -> Result<Self, ProfileError> {
+        match std::env::var(PATH_VAR) {
+            Ok(path) => Self::load(&path),
+            Err(_) => Self::load(DEFAULT_PATH),
+        }
+    }
+
+    /// Load device profiles from a file
+    pub fn load(path: &str) -> Result<Self, ProfileError> {
+        Config::builder()
+            .add_source(File::with_name(path))
+            .build()
+            .and_then(|config| config.try_deserialize::<Settings>())
+            .map_err(|e| ProfileError::Config(e.to_string()))
+    }
+
+    /// Parse device profiles from a string in the given format
+    pub fn parse(content: &str, format: FileFormat) -> Result<Self, ProfileError> {
+        Config::builder()
+            .add_source(File::from_str(content, format))
+            .build()
+            .and_then(|config| config.try_deserialize::<Settings>())
+            .map_err(|e| ProfileError::Config(e.to_string()))
+    }
+
+    /// Look up a device profile by name
+    pub fn device(&self, name: &str) -> Result<&DeviceProfile, ProfileError> {
+        self.devices.get(name).ok_or_else(|| ProfileError::UnknownDevice(String::from(name)))
+    }
+
+    /// Names of all devices in a group, sorted
+    pub fn group(&self, group: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = self.devices.iter()
+            .filter(|(_, profile)| profile.groups.iter().any(|g| g == group))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl DeviceProfile {
+    /// Parse the profile address into IPv4 octets
+    pub fn ipv4(&self) -> Result<[u8; 4], ProfileError> {
+        self.address.parse::<Ipv4Addr>()
+            .map(|address| address.octets())
+            .map_err(|_| ProfileError::InvalidAddress(self.address.clone()))
+    }
+}
+
+impl KeySource {
+    /// Build the key provider described by this source
+    pub fn provider(&self) -> Box<dyn KeyProvider> {
+        match self {
+            KeySource::Literal { value } => Box::new(value.clone()),
+            KeySource::Env { var, encoding } => Box::new(EnvKey { var: var.clone(), encoding: *encoding }),
+            KeySource::File { path, encoding } => Box::new(FileKey { path: path.clone(), encoding: *encoding }),
+            KeySource::Config { path, name, encoding } => Box::new(ConfigKey {
+                path: path.clone(), name: name.clone(), encoding: *encoding
+            }),
+        }
+    }
+}
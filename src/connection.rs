@@ -1,39 +1,105 @@
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{SinkExt, Stream, StreamExt};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, SystemTime};
+use tokio::time::{Instant, interval, sleep, timeout};
+use crate::apps::{App, IconCache, InstallReport};
+use crate::config::{DeviceProfile, ProfileError, Settings};
+use crate::epg::{Program, ProgramGuide};
+use crate::error::RequestError;
+use crate::image::{Image, Screenshot};
+use crate::media::{MediaPlayer, PlayerState};
 use crate::message::ECPMessage;
+use crate::message::notification::Notification;
 use crate::message::request::Request;
 use crate::message::response::Response;
+use crate::key::{KeyError, KeyProvider, SecretKey};
+use crate::macros::{literal_keys, Macro, MacroAbort, MacroReport, Step, StepResult};
+use crate::nodes::NodeStats;
+use crate::perf::{ChannelPerf, PerfSample};
+use crate::protocol::auth::AuthOutcome;
+use crate::protocol::command::Set;
+use crate::protocol::deeplink::DeepLink;
+use crate::protocol::query::Get;
 use crate::protocol::session::ECPSocket;
+use crate::setting::{DeviceSetting, PictureSetting, ResetScope};
+use crate::tracker::{PlaybackEvent, PlaybackTracker};
+use crate::tv::{TvChannel, TvInput};
+use crate::ui::{direction_toward, Selector, UiNode};
+use crate::wait::Condition;
 
 #[derive(Debug)]
 pub struct Connection {
-    pub ipv4:           [u8; 4],
-    pub port:           usize,
-    pub key:            Vec<u8>,
-    pub sync_counter:   i32,
-    pub socket:         Option<ECPSocket>,
+    pub ipv4:               [u8; 4],
+    pub port:               usize,
+    pub key:                SecretKey,
+    pub origin:             String,
+    pub connect_timeout:    Option<Duration>,
+    pub request_timeout:    Option<Duration>,
+    pub icon_cache:         Option<IconCache>,
+    pub app_ids:            HashMap<String, i32>,
+    pub app_versions:       HashMap<i32, String>,
+    pub sync_counter:       i32,
+    pub socket:             Option<ECPSocket>,
+    pub subscribed_events:  Vec<String>,
+    pub notifications:      VecDeque<Notification>,
 }
 
 impl Connection {
     /// Default ECP port
     const DEFAULT_PORT: usize = 8060;
 
+    /// Default WebSocket origin header
+    const DEFAULT_ORIGIN: &'static str = "Android";
+
+    /// Delay between polls in `wait_for` when not subscribed to the condition's event
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Delay between media player polls while confirming playback changes
+    const PLAYBACK_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
     /// Create a new connection object with no socket connection
-    pub fn new(ipv4: [u8; 4], key: Vec<u8>) -> Self {
+    pub fn new(ipv4: [u8; 4], key: impl Into<SecretKey>) -> Self {
         Self {
             ipv4,
             port: Self::DEFAULT_PORT,
-            key,
+            key: key.into(),
+            origin: String::from(Self::DEFAULT_ORIGIN),
+            connect_timeout: None,
+            request_timeout: None,
+            icon_cache: None,
+            app_ids: HashMap::new(),
+            app_versions: HashMap::new(),
             sync_counter: -1,
             socket: None,
+            subscribed_events: vec![],
+            notifications: VecDeque::new(),
         }
     }
 
+    /// Create a new connection object using a key loaded from the given provider
+    pub fn with_key_provider(ipv4: [u8; 4], provider: &dyn KeyProvider) -> Result<Self, KeyError> {
+        Ok(Self::new(ipv4, provider.key()?))
+    }
+
+    /// Create a new connection object from a named device in the default profiles file
+    pub fn from_profile(name: &str) -> Result<Self, ProfileError> {
+        let settings = Settings::load_default()?;
+        Self::from_device_profile(settings.device(name)?)
+    }
+
+    /// Create a new connection object from a device profile
+    pub fn from_device_profile(profile: &DeviceProfile) -> Result<Self, ProfileError> {
+        let mut connection = Self::with_key_provider(profile.ipv4()?, profile.key.provider().as_ref())?;
+        connection.port = profile.port;
+        connection.origin = profile.origin.clone();
+        connection.connect_timeout = profile.timeouts.connect_ms.map(Duration::from_millis);
+        connection.request_timeout = profile.timeouts.request_ms.map(Duration::from_millis);
+        Ok(connection)
+    }
+
     /// Whether or not the connection has been opened
     pub fn is_open(&self) -> bool {
-        match &self.socket {
-            None => false,
-            Some(_) => true,
-        }
+        self.socket.is_some()
     }
 
     /// Whether or not the connection has completed authentication
@@ -51,28 +117,679 @@ impl Connection {
     }
 
     /// Open connection to device and initialize authenticated ECP session
-    pub async fn open(&mut self) -> bool {
-        let mut socket = ECPSocket::open(
-            &format!("{}.{}.{}.{}", self.ipv4[0], self.ipv4[1], self.ipv4[2], self.ipv4[3]),
-            &format!("{}", self.port)
-        ).await;
-
+    pub async fn open(&mut self) -> AuthOutcome {
         let counter = self.next_sync_number();
-        socket.authenticate(&self.key, counter).await;
+        let handshake = async {
+            let mut socket = ECPSocket::open(
+                &format!("{}.{}.{}.{}", self.ipv4[0], self.ipv4[1], self.ipv4[2], self.ipv4[3]),
+                &format!("{}", self.port),
+                &self.origin
+            ).await?;
+            let outcome = socket.authenticate(self.key.expose(), counter).await;
+            Ok::<_, String>((socket, outcome))
+        };
+
+        let handshake = match self.connect_timeout {
+            None => handshake.await,
+            Some(duration) => match timeout(duration, handshake).await {
+                Ok(result) => result,
+                Err(_) => return AuthOutcome::TimedOut,
+            },
+        };
 
-        let result = socket.authenticated;
-        self.socket = Some(socket);
-        result
+        match handshake {
+            Ok((socket, outcome)) => {
+                self.socket = Some(socket);
+                outcome
+            }
+            Err(reason) => AuthOutcome::ConnectFailed { reason },
+        }
     }
 
-    /// Send an ECPMessage request and wait for the next response
+    /// Send an ECPMessage request and wait for its response, queueing any notifications received first
+    /// and dropping late replies to earlier requests and non-text frames
     pub async fn send_request(&mut self, request: Request) -> Option<Response> {
-        match &mut self.socket {
-            None => None,
-            Some(socket) => {
-                let _ = socket.writer.send(request.build().into_message()).await;
-                self.sync_counter+=1;
-                Response::from_message(self.next().await.unwrap())
+        let socket = self.socket.as_mut()?;
+        let _ = socket.writer.send(request.build().into_message()).await;
+        self.sync_counter+=1;
+
+        let request_id = request.request_id();
+        let request_timeout = self.request_timeout;
+        let reply = async {
+            loop {
+                // Control and binary frames aren't replies, so they are passed over
+                let response = match Response::from_message(self.next_non_notification().await?) {
+                    None => continue,
+                    Some(response) => response,
+                };
+                // Replies without a readable id can't be matched, so they are taken as this one's
+                if response.response_id < 0 || response.response_id == request_id {
+                    return Some(response);
+                }
+            }
+        };
+
+        match request_timeout {
+            None => reply.await,
+            Some(duration) => timeout(duration, reply).await.ok().flatten(),
+        }
+    }
+
+    /// Get the next message which isn't a notification, queueing notifications along the way
+    async fn next_non_notification(&mut self) -> Option<ECPMessage> {
+        loop {
+            let message = self.next().await?;
+            if let ECPMessage::Text { text } = &message {
+                if let Some(notification) = Notification::from_text(text) {
+                    self.notifications.push_back(notification);
+                    continue;
+                }
+            }
+            return Some(message);
+        }
+    }
+
+    /// Send a request with the next request-id and return the response only if it succeeded
+    pub async fn request(&mut self, request: impl Into<Request>) -> Result<Response, RequestError> {
+        if !self.is_open() {
+            return Err(RequestError::NotConnected);
+        }
+
+        let request = request.into().set_request_id(self.next_sync_number());
+        let response = self.send_request(request).await.ok_or(RequestError::NoResponse)?;
+        if response.is_success() {
+            Ok(response)
+        }
+        else {
+            Err(RequestError::from_status(response.status_code, &response.status_message))
+        }
+    }
+
+    /// Capture the screen; the device only allows this when enabled in its developer settings
+    pub async fn capture_screen(&mut self) -> Result<Screenshot, RequestError> {
+        let response = self.request(Set::CaptureScreen).await.map_err(|e| match e {
+            RequestError::Disallowed { status_code, status_message } => {
+                RequestError::CaptureDisallowed { status_code, status_message }
+            }
+            e => e,
+        })?;
+        Screenshot::from_response(response)
+    }
+
+    /// List the apps installed on the device
+    pub async fn installed_apps(&mut self) -> Result<Vec<App>, RequestError> {
+        let response = self.request(Get::InstalledApps).await?;
+        App::parse_list(response.text().ok_or_else(|| RequestError::Content(String::from("missing app list")))?)
+    }
+
+    /// Get an app's icon, from the icon cache when one is set and holds this app version
+    pub async fn app_icon(&mut self, channel_id: i32) -> Result<Image, RequestError> {
+        let cache = match self.icon_cache.clone() {
+            None => return Image::from_response(self.request(Get::QueryAppIcon { channel_id }).await?),
+            Some(cache) => cache,
+        };
+
+        // App versions are listed once per session, and again only for an app not seen yet
+        if !self.app_versions.contains_key(&channel_id) {
+            self.app_versions = self.installed_apps().await?
+                .into_iter()
+                .filter_map(|app| Some((app.channel_id()?, app.version)))
+                .collect();
+        }
+        let version = match self.app_versions.get(&channel_id).filter(|version| !version.is_empty()) {
+            // Without a version the icon can't be told apart from other versions', so it isn't cached
+            None => return Image::from_response(self.request(Get::QueryAppIcon { channel_id }).await?),
+            Some(version) => version.clone(),
+        };
+
+        if let Some(icon) = cache.get(channel_id, &version) {
+            return Ok(icon);
+        }
+
+        let icon = Image::from_response(self.request(Get::QueryAppIcon { channel_id }).await?)?;
+        cache.put(channel_id, &version, &icon)?;
+        Ok(icon)
+    }
+
+    /// Press a single key
+    pub async fn press_key(&mut self, key: &str) -> Result<(), RequestError> {
+        self.request(Set::PressKey { key: String::from(key) }).await.map(|_| ())
+    }
+
+    /// Type text into the focused field, one literal key press per character
+    pub async fn type_text(&mut self, text: &str) -> Result<(), RequestError> {
+        for key in literal_keys(text) {
+            self.press_key(&key).await?;
+        }
+        Ok(())
+    }
+
+    /// Run a key macro, stopping at the first failed step or when aborted
+    pub async fn run_macro(&mut self, steps: &Macro, abort: &MacroAbort) -> MacroReport {
+        /// How often to check for an abort while waiting
+        const ABORT_POLL: Duration = Duration::from_millis(50);
+
+        let mut report = MacroReport::default();
+        for step in &steps.steps {
+            if abort.is_aborted() {
+                report.aborted = true;
+                break;
+            }
+
+            let outcome = match step {
+                Step::Press { key } => self.press_key(key).await,
+                Step::Type { text } => {
+                    let mut typed = Ok(());
+                    for key in literal_keys(text) {
+                        if abort.is_aborted() {
+                            break;
+                        }
+                        if let Err(e) = self.press_key(&key).await {
+                            typed = Err(e);
+                            break;
+                        }
+                    }
+                    typed
+                }
+                Step::Wait { duration } => {
+                    let mut remaining = *duration;
+                    while !remaining.is_zero() && !abort.is_aborted() {
+                        let slice = remaining.min(ABORT_POLL);
+                        tokio::time::sleep(slice).await;
+                        remaining -= slice;
+                    }
+                    Ok(())
+                }
+            };
+
+            let failed = outcome.is_err();
+            report.results.push(StepResult { step: step.clone(), outcome });
+            if failed {
+                break;
+            }
+            // An abort may have cut a wait or typing short
+            if abort.is_aborted() {
+                report.aborted = true;
+                break;
+            }
+        }
+        report
+    }
+
+    /// Launch an app, optionally deep linking to content within it
+    pub async fn launch(&mut self, channel_id: i32, deep_link: Option<DeepLink>) -> Result<(), RequestError> {
+        self.request(Set::LaunchApp { channel_id, deep_link }).await.map(|_| ())
+    }
+
+    /// Find an installed app by channel id
+    pub async fn installed_app(&mut self, channel_id: i32) -> Result<Option<App>, RequestError> {
+        Ok(self.installed_apps().await?.into_iter().find(|app| app.channel_id() == Some(channel_id)))
+    }
+
+    /// Whether or not an app is installed
+    pub async fn is_installed(&mut self, channel_id: i32) -> Result<bool, RequestError> {
+        Ok(self.installed_app(channel_id).await?.is_some())
+    }
+
+    /// Open the channel store install flow for an app, then re-query installed apps until it
+    /// appears or its version changes; the store may need the install confirmed on screen.
+    /// An app already installed whose version doesn't change within the time is reported unchanged
+    pub async fn install_app(&mut self, channel_id: i32, within: Duration) -> Result<InstallReport, RequestError> {
+        let previous_version = self.installed_app(channel_id).await?.map(|app| app.version);
+        self.request(Set::InstallApp { channel_id }).await?;
+
+        let deadline = Instant::now() + within;
+        loop {
+            let installed = self.installed_app(channel_id).await?;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if let Some(app) = installed {
+                if previous_version.as_ref() != Some(&app.version) || remaining.is_zero() {
+                    self.app_versions.insert(channel_id, app.version.clone());
+                    return Ok(InstallReport { app, previous_version });
+                }
+            }
+
+            if remaining.is_zero() {
+                return Err(RequestError::TimedOut);
+            }
+            sleep(remaining.min(Self::POLL_INTERVAL)).await;
+        }
+    }
+
+    /// Exit the current app back to the home screen
+    pub async fn exit_to_home(&mut self, within: Duration) -> Result<(), RequestError> {
+        self.press_key("Home").await?;
+        self.wait_for(&Condition::HomeScreen, within).await
+    }
+
+    /// Launch an installed app by name, remembering the resolved channel id for this session
+    pub async fn launch_by_name(&mut self, name: &str) -> Result<i32, RequestError> {
+        let key = name.trim().to_lowercase();
+        let channel_id = match self.app_ids.get(&key) {
+            Some(channel_id) => *channel_id,
+            None => {
+                let apps: Vec<App> = self.installed_apps().await?
+                    .into_iter()
+                    .filter(|app| app.channel_id().is_some())
+                    .collect();
+                let channel_id = App::find_by_name(&apps, name)?.channel_id().unwrap_or_default();
+                self.app_ids.insert(key, channel_id);
+                channel_id
+            }
+        };
+
+        self.launch(channel_id, None).await?;
+        Ok(channel_id)
+    }
+
+    /// Send custom params to the running app
+    pub async fn input(&mut self, params: &[(&str, &str)]) -> Result<(), RequestError> {
+        let params = params.iter().map(|(key, value)| (String::from(*key), String::from(*value))).collect();
+        self.request(Set::Input { params }).await.map(|_| ())
+    }
+
+    /// Fetch the SceneGraph UI tree of the running app
+    pub async fn ui_tree(&mut self) -> Result<UiNode, RequestError> {
+        let response = self.request(Get::AppUi).await?;
+        UiNode::parse(response.text().ok_or_else(|| RequestError::Content(String::from("missing UI tree")))?)
+    }
+
+    /// Move focus with direction keys until a node matching the selector is focused, then press Select
+    pub async fn focus_and_select(&mut self, selector: &Selector, max_moves: usize) -> Result<(), RequestError> {
+        for _ in 0..=max_moves {
+            let tree = self.ui_tree().await?;
+            let target = tree.find(selector)
+                .ok_or_else(|| RequestError::NotFound(format!("{:?}", selector)))?;
+
+            if target.is_focused() {
+                return self.press_key("Select").await;
+            }
+
+            let focused = tree.focused()
+                .ok_or_else(|| RequestError::NotFound(String::from("focused node")))?;
+            let direction = direction_toward(focused, target)
+                .ok_or_else(|| RequestError::Content(String::from("no bounds to navigate toward target")))?;
+            self.press_key(direction).await?;
+        }
+        Err(RequestError::TimedOut)
+    }
+
+    /// Query CPU and memory use of the running channel
+    pub async fn channel_performance(&mut self) -> Result<ChannelPerf, RequestError> {
+        let response = self.request(Get::ChannelPerformance).await?;
+        ChannelPerf::parse(response.text().ok_or_else(|| RequestError::Content(String::from("missing chanperf data")))?)
+    }
+
+    /// Stream channel performance samples taken at a fixed interval
+    pub fn sample_performance(&mut self, every: Duration) -> impl Stream<Item = Result<PerfSample, RequestError>> + '_ {
+        futures_util::stream::unfold((self, interval(every)), |(connection, mut ticker)| async move {
+            ticker.tick().await;
+            let sample = connection.channel_performance().await
+                .map(|perf| PerfSample { timestamp: SystemTime::now(), perf });
+            Some((sample, (connection, ticker)))
+        })
+    }
+
+    /// Query SceneGraph node counts of the running channel
+    pub async fn node_stats(&mut self) -> Result<NodeStats, RequestError> {
+        let response = self.request(Get::SceneGraphNodes).await?;
+        NodeStats::parse(response.text().ok_or_else(|| RequestError::Content(String::from("missing sgnodes data")))?)
+    }
+
+    /// Query the media player status
+    pub async fn media_player(&mut self) -> Result<MediaPlayer, RequestError> {
+        let response = self.request(Get::MediaPlayer).await?;
+        MediaPlayer::parse(response.text().ok_or_else(|| RequestError::Content(String::from("missing media player data")))?)
+    }
+
+    /// Poll the media player until its status passes the check
+    async fn wait_for_player(&mut self, within: Duration, check: impl Fn(&MediaPlayer) -> bool) -> Result<MediaPlayer, RequestError> {
+        let deadline = Instant::now() + within;
+        loop {
+            let player = self.media_player().await?;
+            if check(&player) {
+                return Ok(player);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(RequestError::TimedOut);
+            }
+            sleep(remaining.min(Self::PLAYBACK_POLL_INTERVAL)).await;
+        }
+    }
+
+    /// Resume playback and confirm the player is playing
+    pub async fn play(&mut self, within: Duration) -> Result<MediaPlayer, RequestError> {
+        let player = self.media_player().await?;
+        if player.state == PlayerState::Play {
+            return Ok(player);
+        }
+
+        self.press_key("Play").await?;
+        self.wait_for_player(within, |player| player.state == PlayerState::Play).await
+    }
+
+    /// Pause playback and confirm the player is paused; fails without pressing a key when nothing is playing
+    pub async fn pause(&mut self, within: Duration) -> Result<MediaPlayer, RequestError> {
+        let player = self.media_player().await?;
+        if player.state == PlayerState::Pause {
+            return Ok(player);
+        }
+        if !player.state.is_playing() {
+            return Err(RequestError::Content(String::from("nothing playing")));
+        }
+
+        // Play toggles between playing and paused
+        self.press_key("Play").await?;
+        self.wait_for_player(within, |player| player.state == PlayerState::Pause).await
+    }
+
+    /// Leave playback with Back and confirm the player has stopped
+    pub async fn stop(&mut self, within: Duration) -> Result<MediaPlayer, RequestError> {
+        let player = self.media_player().await?;
+        if player.state.is_stopped() {
+            return Ok(player);
+        }
+
+        self.press_key("Back").await?;
+        self.wait_for_player(within, |player| player.state.is_stopped()).await
+    }
+
+    /// Scan forward or back until the position has moved by at least the offset, then resume playing;
+    /// fails without pressing a key when nothing is playing
+    pub async fn seek_relative(&mut self, offset_ms: i64, within: Duration) -> Result<MediaPlayer, RequestError> {
+        let player = self.media_player().await?;
+        let start = player.position
+            .ok_or_else(|| RequestError::Content(String::from("no playback position")))?;
+        if offset_ms == 0 {
+            return self.play(within).await;
+        }
+        // Scanning starts playback of whatever is loaded, so it is only done while playing
+        if !player.state.is_playing() {
+            return Err(RequestError::Content(String::from("nothing playing")));
+        }
+
+        let target = start.as_millis() as i64 + offset_ms;
+        let key = if offset_ms > 0 { "Fwd" } else { "Rev" };
+        self.press_key(key).await?;
+
+        let reached = self.wait_for_player(within, |player| match player.position {
+            Some(position) if offset_ms > 0 => position.as_millis() as i64 >= target,
+            Some(position) => position.as_millis() as i64 <= target.max(0),
+            None => false,
+        }).await;
+
+        // Leave scan mode even if the target wasn't reached
+        self.press_key("Play").await?;
+        reached?;
+        self.wait_for_player(within, |player| player.state == PlayerState::Play).await
+    }
+
+    /// Stream playback events by polling the media player at a fixed interval
+    pub fn track_playback<'a>(
+        &'a mut self, tracker: &'a mut PlaybackTracker, every: Duration
+    ) -> impl Stream<Item = Result<PlaybackEvent, RequestError>> + 'a {
+        let state = (self, tracker, interval(every), VecDeque::new());
+        futures_util::stream::unfold(state, |(connection, tracker, mut ticker, mut pending)| async move {
+            loop {
+                if let Some(event) = pending.pop_front() {
+                    return Some((Ok(event), (connection, tracker, ticker, pending)));
+                }
+
+                ticker.tick().await;
+                match connection.media_player().await {
+                    Ok(player) => pending.extend(tracker.update(std::time::Instant::now(), &player)),
+                    Err(e) => return Some((Err(e), (connection, tracker, ticker, pending))),
+                }
+            }
+        })
+    }
+
+    /// Query the active TV input
+    pub async fn active_tv_input(&mut self) -> Result<TvInput, RequestError> {
+        let response = self.request(Get::ActiveTvInput).await?;
+        TvInput::parse_active(response.text().ok_or_else(|| RequestError::Content(String::from("missing active input")))?)
+    }
+
+    /// Switch to a TV input with its input key, or by launching its input id when it has no key,
+    /// then re-query the active input until it changes over
+    pub async fn switch_input(&mut self, input: TvInput, within: Duration) -> Result<(), RequestError> {
+        let id = input.id();
+        if self.active_tv_input().await?.id() == id {
+            return Ok(());
+        }
+        match input.key() {
+            Some(key) => self.press_key(key).await?,
+            None => {
+                self.request(Set::LaunchTvInput { input_id: id.clone(), params: vec![] }).await?;
+            }
+        }
+
+        let deadline = Instant::now() + within;
+        loop {
+            if self.active_tv_input().await?.id() == id {
+                return Ok(());
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(RequestError::TimedOut);
+            }
+            sleep(remaining.min(Self::POLL_INTERVAL)).await;
+        }
+    }
+
+    /// List the tuner's TV channels
+    pub async fn tv_channels(&mut self) -> Result<Vec<TvChannel>, RequestError> {
+        let response = self.request(Get::TvChannels).await?;
+        TvChannel::parse_list(response.text().ok_or_else(|| RequestError::Content(String::from("missing channel list")))?)
+    }
+
+    /// Query the TV channel currently tuned
+    pub async fn active_tv_channel(&mut self) -> Result<TvChannel, RequestError> {
+        let response = self.request(Get::ActiveTvChannel).await?;
+        TvChannel::parse_active(response.text().ok_or_else(|| RequestError::Content(String::from("missing active channel")))?)
+    }
+
+    /// Launch the tuner input on a channel number, then re-query the active channel until it is tuned
+    pub async fn tune_channel(&mut self, number: &str, within: Duration) -> Result<TvChannel, RequestError> {
+        self.tune(number, within).await.map(|(channel, _)| channel)
+    }
+
+    /// Tune a channel, returning it along with the program reported in the reply which confirmed it
+    async fn tune(&mut self, number: &str, within: Duration) -> Result<(TvChannel, Option<Program>), RequestError> {
+        self.request(Set::LaunchTvInput {
+            input_id: TvInput::Tuner.id(),
+            params: vec![(String::from("ch"), String::from(number))],
+        }).await?;
+
+        let deadline = Instant::now() + within;
+        loop {
+            // The tuner may report no channel while it is still starting
+            if let Ok(response) = self.request(Get::ActiveTvChannel).await {
+                let xml = response.text().unwrap_or_default();
+                if let Ok(channel) = TvChannel::parse_active(xml) {
+                    if channel.number == number {
+                        return Ok((channel, Program::parse_active(xml).ok().flatten()));
+                    }
+                }
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(RequestError::TimedOut);
+            }
+            sleep(remaining.min(Self::POLL_INTERVAL)).await;
+        }
+    }
+
+    /// Query the program airing on the active TV channel, if it reports one
+    pub async fn active_program(&mut self) -> Result<Option<Program>, RequestError> {
+        let response = self.request(Get::ActiveTvChannel).await?;
+        Program::parse_active(response.text().ok_or_else(|| RequestError::Content(String::from("missing active channel")))?)
+    }
+
+    /// Tune each channel in turn and collect the program reported by the reply confirming the tune;
+    /// programs without a reported start time are given the time they were seen. Channels which fail
+    /// to tune or report are recorded as skipped, and the channel active at the start is tuned again afterwards
+    pub async fn collect_guide(&mut self, channels: &[TvChannel], within: Duration) -> Result<ProgramGuide, RequestError> {
+        let starting = self.active_tv_channel().await.ok();
+        let mut guide = ProgramGuide { channels: channels.to_vec(), ..ProgramGuide::default() };
+        for channel in channels {
+            match self.tune(&channel.number, within).await {
+                Ok((_, Some(mut program))) => {
+                    program.start.get_or_insert_with(SystemTime::now);
+                    guide.programs.push(program);
+                }
+                Ok((_, None)) => {}
+                Err(_) => guide.skipped.push(channel.clone()),
+            }
+        }
+
+        if let Some(starting) = starting {
+            // The guide is still returned if the starting channel can no longer be tuned
+            let _ = self.tune_channel(&starting.number, within).await;
+        }
+        Ok(guide)
+    }
+
+    /// Query all audio settings
+    pub async fn audio_settings(&mut self) -> Result<Vec<DeviceSetting>, RequestError> {
+        let response = self.request(Get::AudioSettings).await?;
+        DeviceSetting::parse_list(response.text().ok_or_else(|| RequestError::Content(String::from("missing audio settings")))?)
+    }
+
+    /// Query one audio setting
+    pub async fn audio_setting(&mut self, id: &str) -> Result<DeviceSetting, RequestError> {
+        let response = self.request(Get::AudioSetting { id: String::from(id) }).await?;
+        DeviceSetting::parse(response.text().ok_or_else(|| RequestError::Content(String::from("missing audio setting")))?)
+    }
+
+    /// Change an audio setting, checking the value against the setting's allowed values first
+    pub async fn set_audio_setting(&mut self, id: &str, value: &str) -> Result<(), RequestError> {
+        self.audio_setting(id).await?.check(value)?;
+        self.request(Set::AudioSetting { id: String::from(id), value: String::from(value) }).await.map(|_| ())
+    }
+
+    /// Reset audio settings to their defaults
+    pub async fn reset_audio_settings(&mut self, scope: ResetScope) -> Result<(), RequestError> {
+        self.request(Set::ResetAudioSettings { scope }).await.map(|_| ())
+    }
+
+    /// Query all picture quality settings
+    pub async fn picture_settings(&mut self) -> Result<Vec<DeviceSetting>, RequestError> {
+        let response = self.request(Get::PictureSettings).await?;
+        DeviceSetting::parse_list(response.text().ok_or_else(|| RequestError::Content(String::from("missing picture settings")))?)
+    }
+
+    /// Query one picture quality setting
+    pub async fn picture_setting(&mut self, setting: PictureSetting) -> Result<DeviceSetting, RequestError> {
+        let response = self.request(Get::PictureSetting { id: String::from(setting.id()) }).await?;
+        DeviceSetting::parse(response.text().ok_or_else(|| RequestError::Content(String::from("missing picture setting")))?)
+    }
+
+    /// Query the color space options
+    pub async fn color_space_settings(&mut self) -> Result<Vec<DeviceSetting>, RequestError> {
+        let response = self.request(Get::ColorSpaceSettings).await?;
+        DeviceSetting::parse_list(response.text().ok_or_else(|| RequestError::Content(String::from("missing color space settings")))?)
+    }
+
+    /// Change a picture quality setting, checking the value against the setting's allowed values first
+    pub async fn set_picture_setting(&mut self, setting: PictureSetting, value: &str) -> Result<(), RequestError> {
+        self.picture_setting(setting).await?.check(value)?;
+        self.request(Set::PictureSetting { id: String::from(setting.id()), value: String::from(value) }).await.map(|_| ())
+    }
+
+    /// Reset picture quality settings to their defaults
+    pub async fn reset_picture_settings(&mut self, scope: ResetScope) -> Result<(), RequestError> {
+        self.request(Set::ResetPictureSettings { scope }).await.map(|_| ())
+    }
+
+    /// Subscribe to notifications for the given events, e.g. "media-player-state-changed"
+    pub async fn subscribe(&mut self, events: &[&str]) -> Result<(), RequestError> {
+        let mut subscribed = self.subscribed_events.clone();
+        for event in events {
+            if !subscribed.iter().any(|existing| existing == event) {
+                subscribed.push(String::from(*event));
+            }
+        }
+
+        self.request(Set::RequestEvents { events: subscribed.clone() }).await?;
+        self.subscribed_events = subscribed;
+        Ok(())
+    }
+
+    /// Whether or not notifications for the given event have been requested
+    pub fn is_subscribed(&self, event: &str) -> bool {
+        self.subscribed_events.iter().any(|subscribed| subscribed == event)
+    }
+
+    /// Get the next queued or received notification
+    pub async fn next_notification(&mut self) -> Option<Notification> {
+        if let Some(notification) = self.notifications.pop_front() {
+            return Some(notification);
+        }
+
+        loop {
+            if let ECPMessage::Text { text } = self.next().await? {
+                if let Some(notification) = Notification::from_text(&text) {
+                    return Some(notification);
+                }
+            }
+        }
+    }
+
+    /// Read messages until a notification for the event arrives, queueing every notification
+    /// for `next_notification`
+    async fn receive_event(&mut self, event: &str) -> Option<()> {
+        loop {
+            if let ECPMessage::Text { text } = self.next().await? {
+                if let Some(notification) = Notification::from_text(&text) {
+                    let matched = notification.event == event;
+                    self.notifications.push_back(notification);
+                    if matched {
+                        return Some(());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check a condition once by sending its query
+    pub async fn check(&mut self, condition: &Condition) -> Result<bool, RequestError> {
+        let response = self.request(condition.query()).await?;
+        Ok(condition.is_met(response.text().unwrap_or_default()))
+    }
+
+    /// Wait until a condition holds, re-checking on its notification when subscribed and by polling
+    pub async fn wait_for(&mut self, condition: &Condition, within: Duration) -> Result<(), RequestError> {
+        let deadline = Instant::now() + within;
+        loop {
+            let queued = self.notifications.len();
+            if self.check(condition).await? {
+                return Ok(());
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(RequestError::TimedOut);
+            }
+
+            // The notification may have been queued while the check was waiting for its reply
+            let notified = self.notifications.iter()
+                .skip(queued)
+                .any(|notification| notification.event == condition.event());
+            if notified {
+                continue;
+            }
+
+            // Notifications aren't sent for every change, so polling carries on between them
+            if self.is_subscribed(condition.event()) {
+                let _ = timeout(remaining.min(Self::POLL_INTERVAL), self.receive_event(condition.event())).await;
+            }
+            else {
+                sleep(remaining.min(Self::POLL_INTERVAL)).await;
             }
         }
     }
@@ -83,10 +800,11 @@ impl Connection {
             None => None,
             Some(socket) => {
                 loop {
-                    if let Some(socket_message) = socket.reader.next().await {
-                        if let Ok(message) = socket_message {
-                            return Some(ECPMessage::from_message(message))
-                        }
+                    match socket.reader.next().await {
+                        Some(Ok(message)) => return Some(ECPMessage::from_message(message)),
+                        Some(Err(_)) => continue,
+                        // Socket closed
+                        None => return None,
                     }
                 }
             }
@@ -97,6 +815,12 @@ impl Connection {
 impl Clone for Connection {
     /// Create a new unopened connection identical to the given one
     fn clone(&self) -> Self {
-        Connection::new(self.ipv4, self.key.clone())
+        let mut connection = Connection::new(self.ipv4, self.key.clone());
+        connection.port = self.port;
+        connection.origin = self.origin.clone();
+        connection.connect_timeout = self.connect_timeout;
+        connection.request_timeout = self.request_timeout;
+        connection.icon_cache = self.icon_cache.clone();
+        connection
     }
 }
\ No newline at end of file
@@ -0,0 +1,182 @@
+use futures_util::Stream;
+use std::collections::VecDeque;
+use std::time::SystemTime;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+
+use crate::connection::Connection;
+use crate::error::RequestError;
+
+/// Single line of console output with the time it was received
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LogLine {
+    pub received:   SystemTime,
+    pub text:       String,
+}
+
+/// Crash block collected from the debugger output
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CrashReport {
+    pub received:   SystemTime,
+    pub error:      Option<String>,
+    pub backtrace:  Vec<String>,
+    pub lines:      Vec<String>,
+}
+
+/// Output from the debug console
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConsoleEvent {
+    Line(LogLine),
+    Crash(CrashReport),
+}
+
+/// Groups lines between a runtime error and the debugger prompt into crash reports
+#[derive(Clone, Debug, Default)]
+pub struct CrashDetector {
+    block: Option<CrashReport>,
+    in_backtrace: bool,
+}
+
+impl CrashDetector {
+    /// Feed a line, returning a crash report when its block ends
+    pub fn push(&mut self, line: &LogLine) -> Option<CrashReport> {
+        let text = line.text.trim();
+
+        if self.block.is_none() {
+            let starts_block = text.contains("BrightScript Micro Debugger")
+                || text.contains("(runtime error")
+                || text == "Backtrace:";
+            if !starts_block {
+                return None;
+            }
+            self.block = Some(CrashReport {
+                received: line.received,
+                error: None,
+                backtrace: vec![],
+                lines: vec![],
+            });
+        }
+
+        let block = self.block.as_mut()?;
+        block.lines.push(String::from(&line.text));
+
+        if text.contains("(runtime error") && block.error.is_none() {
+            block.error = Some(String::from(text));
+        }
+
+        if text == "Backtrace:" {
+            self.in_backtrace = true;
+        }
+        else if text.ends_with(':') && !text.starts_with("file/line") {
+            // Next section header, e.g. "Local Variables:"
+            self.in_backtrace = false;
+        }
+        else if self.in_backtrace && !text.is_empty() {
+            block.backtrace.push(String::from(text));
+        }
+
+        if text.ends_with("Debugger>") {
+            self.in_backtrace = false;
+            return self.block.take();
+        }
+        None
+    }
+}
+
+/// Client for the BrightScript debug console of a running dev channel
+#[derive(Debug)]
+pub struct DebugConsole {
+    reader:     OwnedReadHalf,
+    writer:     OwnedWriteHalf,
+    buffer:     Vec<u8>,
+    pending:    VecDeque<ConsoleEvent>,
+    detector:   CrashDetector,
+}
+
+impl DebugConsole {
+    /// Default BrightScript console port
+    pub const DEFAULT_PORT: u16 = 8085;
+
+    /// Connect to a console at the given host:port
+    pub async fn connect(address: &str) -> Result<Self, RequestError> {
+        let stream = TcpStream::connect(address).await.map_err(|e| RequestError::Io(e.to_string()))?;
+        let (reader, writer) = stream.into_split();
+        Ok(Self {
+            reader,
+            writer,
+            buffer: vec![],
+            pending: VecDeque::new(),
+            detector: CrashDetector::default(),
+        })
+    }
+
+    /// Connect to the console on the same device as a connection
+    pub async fn for_connection(connection: &Connection) -> Result<Self, RequestError> {
+        let ipv4 = connection.ipv4;
+        Self::connect(&format!("{}.{}.{}.{}:{}", ipv4[0], ipv4[1], ipv4[2], ipv4[3], Self::DEFAULT_PORT)).await
+    }
+
+    /// Send a console command such as "bt" or "cont"
+    pub async fn send_command(&mut self, command: &str) -> Result<(), RequestError> {
+        self.writer.write_all(format!("{}\r\n", command).as_bytes()).await
+            .map_err(|e| RequestError::Io(e.to_string()))
+    }
+
+    /// Get the next line or crash report, or None once the console closes
+    pub async fn next_event(&mut self) -> Option<ConsoleEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+
+            if let Some(text) = self.take_line() {
+                self.queue_line(text);
+                continue;
+            }
+
+            let mut chunk = [0; 4096];
+            match self.reader.read(&mut chunk).await {
+                Ok(0) | Err(_) => {
+                    // Flush any unterminated output before closing
+                    if self.buffer.is_empty() {
+                        return None;
+                    }
+                    let text = String::from_utf8_lossy(&self.buffer).into_owned();
+                    self.buffer.clear();
+                    self.queue_line(text);
+                }
+                Ok(read) => self.buffer.extend_from_slice(&chunk[..read]),
+            }
+        }
+    }
+
+    /// Stream console events until the console closes
+    pub fn events(&mut self) -> impl Stream<Item = ConsoleEvent> + '_ {
+        futures_util::stream::unfold(self, |console| async move {
+            console.next_event().await.map(|event| (event, console))
+        })
+    }
+
+    /// Take a complete line, or a debugger prompt which isn't newline terminated
+    fn take_line(&mut self) -> Option<String> {
+        let end = match self.buffer.iter().position(|b| *b == b'\n') {
+            Some(position) => position + 1,
+            None if String::from_utf8_lossy(&self.buffer).trim_end().ends_with("Debugger>") => self.buffer.len(),
+            None => return None,
+        };
+
+        let line: Vec<u8> = self.buffer.drain(..end).collect();
+        Some(String::from(String::from_utf8_lossy(&line).trim_end_matches(['\r', '\n'])))
+    }
+
+    /// Queue a line event and any crash report it completes
+    fn queue_line(&mut self, text: String) {
+        let line = LogLine { received: SystemTime::now(), text };
+        let crash = self.detector.push(&line);
+        self.pending.push_back(ConsoleEvent::Line(line));
+        if let Some(crash) = crash {
+            self.pending.push_back(ConsoleEvent::Crash(crash));
+        }
+    }
+}
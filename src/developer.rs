@@ -0,0 +1,176 @@
+use rand::prelude::*;
+use std::time::Duration;
+use zeroize::Zeroizing;
+
+use crate::connection::Connection;
+use crate::error::RequestError;
+use crate::http::{digest_authorization, parse_digest_challenge, send};
+use crate::key::SecretKey;
+
+/// Outcome reported by the developer installer's result page
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InstallerResult {
+    Success { messages: Vec<String> },
+    Error { messages: Vec<String> },
+}
+
+impl InstallerResult {
+    /// Whether or not the installer reported success
+    pub fn is_success(&self) -> bool {
+        matches!(self, InstallerResult::Success { .. })
+    }
+
+    /// Parse the installer's HTML result page
+    pub fn from_page(html: &str) -> Self {
+        let messages = Self::page_messages(html);
+
+        let failed = messages.iter().any(|(kind, text)| {
+            kind == "error" || text.contains("Failure") || text.contains("Failed") || text.contains("Error")
+        });
+        let messages: Vec<String> = messages.into_iter().map(|(_, text)| text).collect();
+
+        if failed || messages.is_empty() {
+            InstallerResult::Error { messages }
+        }
+        else {
+            InstallerResult::Success { messages }
+        }
+    }
+
+    /// Collect (type, content) message pairs from the page scripts, or red font text on older firmware
+    fn page_messages(html: &str) -> Vec<(String, String)> {
+        let mut messages = vec![];
+
+        let mut rest = html;
+        while let Some(start) = rest.find("'Set message type'") {
+            rest = &rest[start..];
+            let kind = quoted_argument(rest).unwrap_or_default();
+            let content = match rest.find("'Set message content'") {
+                Some(position) => quoted_argument(&rest[position..]).unwrap_or_default(),
+                None => String::new(),
+            };
+            messages.push((kind, content));
+            rest = &rest["'Set message type'".len()..];
+        }
+
+        if messages.is_empty() {
+            let mut rest = html;
+            while let Some(start) = rest.find("<font color=\"red\">") {
+                rest = &rest[start + "<font color=\"red\">".len()..];
+                let end = rest.find("</font>").unwrap_or(rest.len());
+                messages.push((String::new(), String::from(rest[..end].trim())));
+                rest = &rest[end..];
+            }
+        }
+
+        messages
+    }
+}
+
+/// Second single-quoted argument of a `trigger('name', 'value')` call
+fn quoted_argument(call: &str) -> Option<String> {
+    let after_name = call.splitn(3, '\'').nth(2)?;
+    let value = after_name.split_once('\'')?.1;
+    Some(String::from(value.split_once('\'')?.0))
+}
+
+/// Client for the developer application installer served on port 80 in developer mode
+#[derive(Clone, Debug)]
+pub struct DeveloperClient {
+    pub address:    String,
+    pub username:   String,
+    pub password:   SecretKey,
+    pub timeout:    Duration,
+}
+
+impl DeveloperClient {
+    /// Default developer installer username
+    const DEFAULT_USERNAME: &'static str = "rokudev";
+
+    /// Installer endpoint
+    const INSTALL_PATH: &'static str = "/plugin_install";
+
+    /// Default limit for each installer exchange, long enough for the device to install a package
+    const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+    /// Create a client for the installer at the given host:port
+    pub fn new(address: &str, password: impl Into<SecretKey>) -> Self {
+        Self {
+            address: String::from(address),
+            username: String::from(Self::DEFAULT_USERNAME),
+            password: password.into(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Create a client for the installer on the same device as a connection
+    pub fn for_connection(connection: &Connection, password: impl Into<SecretKey>) -> Self {
+        let ipv4 = connection.ipv4;
+        Self::new(&format!("{}.{}.{}.{}:80", ipv4[0], ipv4[1], ipv4[2], ipv4[3]), password)
+    }
+
+    /// Upload and install a zipped channel package
+    pub async fn install(&self, package: &[u8]) -> Result<InstallerResult, RequestError> {
+        self.submit("Install", Some(package)).await
+    }
+
+    /// Upload a zipped channel package, replacing the current dev channel
+    pub async fn replace(&self, package: &[u8]) -> Result<InstallerResult, RequestError> {
+        self.submit("Replace", Some(package)).await
+    }
+
+    /// Delete the current dev channel
+    pub async fn delete(&self) -> Result<InstallerResult, RequestError> {
+        self.submit("Delete", None).await
+    }
+
+    /// Read a zipped channel package from disk and install it
+    pub async fn install_file(&self, path: impl AsRef<std::path::Path>) -> Result<InstallerResult, RequestError> {
+        let package = std::fs::read(path).map_err(|e| RequestError::Io(e.to_string()))?;
+        self.install(&package).await
+    }
+
+    /// Post an installer form, answering the digest auth challenge first
+    async fn submit(&self, action: &str, package: Option<&[u8]>) -> Result<InstallerResult, RequestError> {
+        let password = Zeroizing::new(String::from_utf8_lossy(self.password.expose()).into_owned());
+
+        // An empty request gets the digest challenge without uploading the package twice
+        let challenge = send(&self.address, "POST", Self::INSTALL_PATH, &[], &[], self.timeout).await?;
+        let header = challenge.headers.get("www-authenticate")
+            .filter(|_| challenge.status_code == 401)
+            .ok_or_else(|| RequestError::Content(String::from("installer did not request digest auth")))?;
+        let authorization = digest_authorization(
+            &parse_digest_challenge(header), &self.username, &password, "POST", Self::INSTALL_PATH
+        );
+
+        let boundary = format!("----ecp{}", hex::encode(thread_rng().gen::<[u8; 12]>()));
+        let body = multipart_body(&boundary, action, package);
+        let headers = vec![
+            (String::from("Authorization"), authorization),
+            (String::from("Content-Type"), format!("multipart/form-data; boundary={}", boundary)),
+        ];
+
+        let response = send(&self.address, "POST", Self::INSTALL_PATH, &headers, &body, self.timeout).await?;
+        match response.status_code {
+            200 => Ok(InstallerResult::from_page(&String::from_utf8_lossy(&response.body))),
+            status_code => Err(RequestError::from_status(status_code, &response.status_message)),
+        }
+    }
+}
+
+/// Build the installer form with the submit action and package archive
+fn multipart_body(boundary: &str, action: &str, package: Option<&[u8]>) -> Vec<u8> {
+    let mut body = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"mysubmit\"\r\n\r\n{}\r\n",
+        boundary, action
+    ).into_bytes();
+
+    let filename = if package.is_some() { "channel.zip" } else { "" };
+    body.extend_from_slice(format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"archive\"; filename=\"{}\"\r\nContent-Type: application/zip\r\n\r\n",
+        boundary, filename
+    ).as_bytes());
+    body.extend_from_slice(package.unwrap_or_default());
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
@@ -0,0 +1,118 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::RequestError;
+use crate::tv::TvChannel;
+use crate::xml::xml_escape;
+
+/// Program airing on a TV channel, from the tv-active-channel query
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Program {
+    pub channel_number: String,
+    pub title:          String,
+    pub description:    Option<String>,
+    pub start:          Option<SystemTime>,
+    pub duration:       Option<Duration>,
+    pub rating:         Option<String>,
+}
+
+impl Program {
+    /// Parse the program from the tv-active-channel XML, if the channel reports one
+    pub fn parse_active(xml: &str) -> Result<Option<Program>, RequestError> {
+        let document = roxmltree::Document::parse(xml).map_err(|e| RequestError::Content(e.to_string()))?;
+        let channel = match document.descendants().find(|node| node.has_tag_name("channel")) {
+            None => return Ok(None),
+            Some(channel) => channel,
+        };
+
+        let text = |tag: &str| channel.children()
+            .find(|node| node.has_tag_name(tag))
+            .and_then(|node| node.text())
+            .map(|text| String::from(text.trim()))
+            .filter(|text| !text.is_empty());
+        let seconds = |tag: &str| text(tag).and_then(|value| value.parse::<u64>().ok());
+
+        Ok(text("program-title").map(|title| Program {
+            channel_number: text("number").unwrap_or_default(),
+            title,
+            description: text("program-description"),
+            start: seconds("program-start-time").map(|start| UNIX_EPOCH + Duration::from_secs(start)),
+            duration: seconds("program-duration").map(Duration::from_secs),
+            rating: text("program-ratings"),
+        }))
+    }
+
+    /// End time, when both start and duration are known
+    pub fn stop(&self) -> Option<SystemTime> {
+        Some(self.start? + self.duration?)
+    }
+}
+
+/// Program data collected across channels
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ProgramGuide {
+    pub channels:   Vec<TvChannel>,
+    pub programs:   Vec<Program>,
+    pub skipped:    Vec<TvChannel>,
+}
+
+impl ProgramGuide {
+    /// XMLTV document listing every channel and each program with a known start time
+    pub fn to_xmltv(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE tv SYSTEM \"xmltv.dtd\">\n<tv generator-info-name=\"ecp\">\n");
+        for channel in &self.channels {
+            xml.push_str(&format!("  <channel id=\"{}\">\n", xml_escape(&channel.number)));
+            if !channel.name.is_empty() {
+                xml.push_str(&format!("    <display-name>{}</display-name>\n", xml_escape(&channel.name)));
+            }
+            xml.push_str(&format!("    <display-name>{}</display-name>\n", xml_escape(&channel.number)));
+            xml.push_str("  </channel>\n");
+        }
+
+        for program in &self.programs {
+            let start = match program.start {
+                None => continue,
+                Some(start) => start,
+            };
+            xml.push_str(&format!("  <programme start=\"{}\"", xmltv_time(start)));
+            if let Some(stop) = program.stop() {
+                xml.push_str(&format!(" stop=\"{}\"", xmltv_time(stop)));
+            }
+            xml.push_str(&format!(" channel=\"{}\">\n", xml_escape(&program.channel_number)));
+            xml.push_str(&format!("    <title>{}</title>\n", xml_escape(&program.title)));
+            if let Some(description) = &program.description {
+                xml.push_str(&format!("    <desc>{}</desc>\n", xml_escape(description)));
+            }
+            if let Some(duration) = program.duration {
+                xml.push_str(&format!("    <length units=\"seconds\">{}</length>\n", duration.as_secs()));
+            }
+            if let Some(rating) = &program.rating {
+                xml.push_str(&format!("    <rating>\n      <value>{}</value>\n    </rating>\n", xml_escape(rating)));
+            }
+            xml.push_str("  </programme>\n");
+        }
+        xml.push_str("</tv>\n");
+        xml
+    }
+}
+
+/// Format a time as XMLTV's "YYYYMMDDhhmmss +0000" in UTC
+fn xmltv_time(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0);
+    let (days, of_day) = (seconds / 86400, seconds % 86400);
+
+    // Civil date from days since the epoch
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02} +0000",
+        year, month, day, of_day / 3600, of_day % 3600 / 60, of_day % 60
+    )
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+
+/// Failure of a request sent over an open connection
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RequestError {
+    NotConnected,
+    NoResponse,
+    Status { status_code: i32, status_message: String },
+    Disallowed { status_code: i32, status_message: String },
+    CaptureDisallowed { status_code: i32, status_message: String },
+    Content(String),
+    Io(String),
+    TimedOut,
+    UnknownApp { name: String },
+    AmbiguousApp { name: String, candidates: Vec<String> },
+    NotFound(String),
+    InvalidSetting { id: String, value: String },
+}
+
+impl RequestError {
+    /// Build the error for a response with a failure status code
+    pub fn from_status(status_code: i32, status_message: &str) -> Self {
+        match status_code {
+            401 | 403 => RequestError::Disallowed { status_code, status_message: String::from(status_message) },
+            _ => RequestError::Status { status_code, status_message: String::from(status_message) },
+        }
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::NotConnected => write!(f, "connection is not open"),
+            RequestError::NoResponse => write!(f, "no response received"),
+            RequestError::Status { status_code, status_message } => {
+                write!(f, "request failed: {} {}", status_code, status_message)
+            }
+            RequestError::Disallowed { status_code, status_message } => {
+                write!(f, "request not allowed by device: {} {}", status_code, status_message)
+            }
+            RequestError::CaptureDisallowed { status_code, status_message } => write!(
+                f,
+                "screen capture not allowed by device ({} {}); enable screen capture in its developer settings",
+                status_code, status_message
+            ),
+            RequestError::Content(e) => write!(f, "unexpected response content: {}", e),
+            RequestError::Io(e) => write!(f, "i/o error: {}", e),
+            RequestError::TimedOut => write!(f, "timed out"),
+            RequestError::UnknownApp { name } => write!(f, "no installed app matches {}", name),
+            RequestError::AmbiguousApp { name, candidates } => {
+                write!(f, "{} matches several apps: {}", name, candidates.join(", "))
+            }
+            RequestError::NotFound(what) => write!(f, "not found: {}", what),
+            RequestError::InvalidSetting { id, value } => write!(f, "{} is not an allowed value for {}", value, id),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
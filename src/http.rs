@@ -0,0 +1,145 @@
+use md5::{Digest, Md5};
+use rand::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use crate::error::RequestError;
+
+/// Minimal HTTP/1.1 response
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct HttpResponse {
+    pub status_code:    i32,
+    pub status_message: String,
+    pub headers:        HashMap<String, String>,
+    pub body:           Vec<u8>,
+}
+
+/// Send a single request over a fresh connection and read the whole response, giving up after a time limit
+pub(crate) async fn send(
+    address: &str, method: &str, path: &str, headers: &[(String, String)], body: &[u8], limit: Duration
+) -> Result<HttpResponse, RequestError> {
+    let io_error = |e: std::io::Error| RequestError::Io(e.to_string());
+    let exchange = async {
+        let mut stream = TcpStream::connect(address).await.map_err(io_error)?;
+
+        let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n", method, path, address, body.len());
+        for (name, value) in headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+
+        stream.write_all(head.as_bytes()).await.map_err(io_error)?;
+        stream.write_all(body).await.map_err(io_error)?;
+
+        let mut raw = vec![];
+        stream.read_to_end(&mut raw).await.map_err(io_error)?;
+        Ok(raw)
+    };
+
+    let raw = timeout(limit, exchange).await.map_err(|_| RequestError::TimedOut)??;
+    parse_response(&raw)
+}
+
+/// Parse a raw HTTP response
+pub(crate) fn parse_response(raw: &[u8]) -> Result<HttpResponse, RequestError> {
+    let split = raw.windows(4).position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| RequestError::Content(String::from("incomplete HTTP response")))?;
+    let head = String::from_utf8_lossy(&raw[..split]);
+    let mut lines = head.split("\r\n");
+
+    let status_line = lines.next().unwrap_or_default();
+    let mut parts = status_line.splitn(3, ' ');
+    let _version = parts.next();
+    let status_code = parts.next().and_then(|code| code.parse::<i32>().ok())
+        .ok_or_else(|| RequestError::Content(format!("bad HTTP status line: {}", status_line)))?;
+    let status_message = String::from(parts.next().unwrap_or_default());
+
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), String::from(value.trim())))
+        .collect();
+
+    let mut body = raw[split + 4..].to_vec();
+    if headers.get("transfer-encoding").map(|value| value.eq_ignore_ascii_case("chunked")) == Some(true) {
+        body = dechunk(&body);
+    }
+
+    Ok(HttpResponse { status_code, status_message, headers, body })
+}
+
+/// Join the chunks of a chunked transfer-encoded body
+fn dechunk(mut body: &[u8]) -> Vec<u8> {
+    let mut joined = vec![];
+    while let Some(line_end) = body.windows(2).position(|window| window == b"\r\n") {
+        let size_text = String::from_utf8_lossy(&body[..line_end]);
+        let size = usize::from_str_radix(size_text.split(';').next().unwrap_or_default().trim(), 16).unwrap_or(0);
+        let start = line_end + 2;
+        if size == 0 || start + size > body.len() {
+            break;
+        }
+        joined.extend_from_slice(&body[start..start + size]);
+        body = &body[(start + size + 2).min(body.len())..];
+    }
+    joined
+}
+
+/// Parse the key="value" pairs of a Digest WWW-Authenticate challenge
+pub(crate) fn parse_digest_challenge(header: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let mut rest = header.trim().strip_prefix("Digest").unwrap_or(header).trim();
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_lowercase();
+        let (value, remainder) = match after.trim_start().strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((value, remainder)) => (value, remainder),
+                None => (quoted, ""),
+            },
+            None => match after.split_once(',') {
+                Some((value, remainder)) => (value.trim(), remainder),
+                None => (after.trim(), ""),
+            },
+        };
+        params.insert(key, String::from(value));
+        rest = remainder.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    }
+    params
+}
+
+/// MD5 hex digest of the given text
+fn md5_hex(text: &str) -> String {
+    hex::encode(Md5::digest(text.as_bytes()))
+}
+
+/// Compute the Digest response hash for qop=auth
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn digest_response(
+    username: &str, password: &str, realm: &str, nonce: &str,
+    method: &str, uri: &str, nc: &str, cnonce: &str
+) -> String {
+    let ha1 = md5_hex(&format!("{}:{}:{}", username, realm, password));
+    let ha2 = md5_hex(&format!("{}:{}", method, uri));
+    md5_hex(&format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2))
+}
+
+/// Build the Authorization header answering a Digest challenge
+pub(crate) fn digest_authorization(
+    challenge: &HashMap<String, String>, username: &str, password: &str, method: &str, uri: &str
+) -> String {
+    let realm = challenge.get("realm").map(String::as_str).unwrap_or_default();
+    let nonce = challenge.get("nonce").map(String::as_str).unwrap_or_default();
+    let nc = "00000001";
+    let cnonce = hex::encode(thread_rng().gen::<[u8; 8]>());
+    let response = digest_response(username, password, realm, nonce, method, uri, nc, &cnonce);
+
+    let mut header = format!(
+        "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", qop=auth, nc={}, cnonce=\"{}\", response=\"{}\"",
+        username, realm, nonce, uri, nc, cnonce, response
+    );
+    if let Some(opaque) = challenge.get("opaque") {
+        header.push_str(&format!(", opaque=\"{}\"", opaque));
+    }
+    header
+}
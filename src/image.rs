@@ -0,0 +1,99 @@
+use std::path::Path;
+
+use crate::error::RequestError;
+use crate::message::{ContentData, ContentType};
+use crate::message::response::Response;
+
+/// Decoded image returned by the device
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Image {
+    pub format:     ContentType,
+    pub bytes:      Vec<u8>,
+    pub width:      Option<u32>,
+    pub height:     Option<u32>,
+}
+
+/// Image of the device's screen
+pub type Screenshot = Image;
+
+impl Image {
+    /// Create an image from raw bytes, reading the dimensions from the header
+    pub fn new(format: ContentType, bytes: Vec<u8>) -> Self {
+        let (width, height) = match Self::read_dimensions(&bytes) {
+            Some((width, height)) => (Some(width), Some(height)),
+            None => (None, None),
+        };
+
+        Self { format, bytes, width, height }
+    }
+
+    /// Create an image from raw bytes, detecting the format from its signature
+    pub fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
+        let format = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            ContentType::Png
+        }
+        else if bytes.starts_with(&[0xFF, 0xD8]) {
+            ContentType::Jpeg
+        }
+        else {
+            return None;
+        };
+
+        Some(Self::new(format, bytes))
+    }
+
+    /// Extract image data from a response
+    pub fn from_response(response: Response) -> Result<Self, RequestError> {
+        match (response.content_type, response.content_data) {
+            (Some(format @ (ContentType::Jpeg | ContentType::Png)), Some(ContentData::Data { bytes })) => {
+                Ok(Self::new(format, bytes))
+            }
+            (content_type, _) => Err(RequestError::Content(format!("expected image data, got {:?}", content_type))),
+        }
+    }
+
+    /// File extension for this image's format
+    pub fn extension(&self) -> &str {
+        match self.format {
+            ContentType::Png => "png",
+            _ => "jpg",
+        }
+    }
+
+    /// Write the image bytes to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), RequestError> {
+        std::fs::write(path, &self.bytes).map_err(|e| RequestError::Io(e.to_string()))
+    }
+
+    /// Read width and height from a PNG or JPEG header
+    pub fn read_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            // IHDR is always the first chunk
+            let width = u32::from_be_bytes(bytes.get(16..20)?.try_into().ok()?);
+            let height = u32::from_be_bytes(bytes.get(20..24)?.try_into().ok()?);
+            return Some((width, height));
+        }
+
+        if bytes.starts_with(&[0xFF, 0xD8]) {
+            // Walk segments until a start-of-frame marker
+            let mut offset = 2;
+            while offset + 4 <= bytes.len() {
+                if bytes[offset] != 0xFF {
+                    return None;
+                }
+                let marker = bytes[offset + 1];
+                let length = u16::from_be_bytes([bytes[offset + 2], bytes[offset + 3]]) as usize;
+                let is_frame = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+                if is_frame {
+                    let frame = bytes.get(offset + 5..offset + 9)?;
+                    let height = u16::from_be_bytes([frame[0], frame[1]]) as u32;
+                    let width = u16::from_be_bytes([frame[2], frame[3]]) as u32;
+                    return Some((width, height));
+                }
+                offset += 2 + length;
+            }
+        }
+
+        None
+    }
+}
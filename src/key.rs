@@ -0,0 +1,178 @@
+use config::{Config, File};
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+use zeroize::Zeroize;
+
+/// Auth key bytes which are wiped on drop and never printed
+#[derive(Clone, Deserialize, Eq, PartialEq)]
+#[serde(from = "String")]
+pub struct SecretKey {
+    bytes: Vec<u8>,
+}
+
+impl SecretKey {
+    /// Wrap raw key bytes
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    /// Borrow the raw key bytes
+    pub fn expose(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretKey(<redacted>)")
+    }
+}
+
+impl From<Vec<u8>> for SecretKey {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl From<String> for SecretKey {
+    fn from(string: String) -> Self {
+        Self::new(string.into_bytes())
+    }
+}
+
+/// How key material is stored in its source
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyEncoding {
+    Raw,
+    Hex,
+    Base64,
+}
+
+impl KeyEncoding {
+    /// Decode stored key material into raw key bytes
+    pub fn decode(&self, stored: &[u8]) -> Result<Vec<u8>, KeyError> {
+        match self {
+            KeyEncoding::Raw => Ok(stored.to_vec()),
+            KeyEncoding::Hex => {
+                let text = String::from_utf8_lossy(stored);
+                hex::decode(text.trim()).map_err(|e| KeyError::Decode(e.to_string()))
+            }
+            KeyEncoding::Base64 => {
+                let text = String::from_utf8_lossy(stored);
+                base64::decode(text.trim()).map_err(|e| KeyError::Decode(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Failure to obtain a key from a provider
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum KeyError {
+    NotFound(String),
+    Io(String),
+    Decode(String),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::NotFound(what) => write!(f, "key not found: {}", what),
+            KeyError::Io(e) => write!(f, "unable to read key: {}", e),
+            KeyError::Decode(e) => write!(f, "unable to decode key: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+/// Source of the auth key used to answer the ECP challenge
+pub trait KeyProvider {
+    /// Load the key
+    fn key(&self) -> Result<SecretKey, KeyError>;
+}
+
+/// Key given directly, held in a `SecretKey` so it is wiped along with the provider
+pub struct LiteralKey {
+    pub key: SecretKey,
+}
+
+impl LiteralKey {
+    /// Wrap raw key bytes
+    pub fn new(bytes: impl Into<SecretKey>) -> Self {
+        Self { key: bytes.into() }
+    }
+}
+
+impl KeyProvider for LiteralKey {
+    fn key(&self) -> Result<SecretKey, KeyError> {
+        Ok(self.key.clone())
+    }
+}
+
+impl KeyProvider for SecretKey {
+    fn key(&self) -> Result<SecretKey, KeyError> {
+        Ok(self.clone())
+    }
+}
+
+/// Key read from an environment variable
+pub struct EnvKey {
+    pub var:        String,
+    pub encoding:   KeyEncoding,
+}
+
+impl KeyProvider for EnvKey {
+    fn key(&self) -> Result<SecretKey, KeyError> {
+        let mut value = std::env::var(&self.var)
+            .map_err(|_| KeyError::NotFound(format!("environment variable {}", self.var)))?;
+        let decoded = self.encoding.decode(value.as_bytes());
+        value.zeroize();
+        decoded.map(SecretKey::new)
+    }
+}
+
+/// Key read from a file
+pub struct FileKey {
+    pub path:       PathBuf,
+    pub encoding:   KeyEncoding,
+}
+
+impl KeyProvider for FileKey {
+    fn key(&self) -> Result<SecretKey, KeyError> {
+        let mut stored = std::fs::read(&self.path)
+            .map_err(|e| KeyError::Io(format!("{}: {}", self.path.display(), e)))?;
+        let decoded = self.encoding.decode(&stored);
+        stored.zeroize();
+        decoded.map(SecretKey::new)
+    }
+}
+
+/// Key read from a named entry in a config file
+pub struct ConfigKey {
+    pub path:       String,
+    pub name:       String,
+    pub encoding:   KeyEncoding,
+}
+
+impl KeyProvider for ConfigKey {
+    fn key(&self) -> Result<SecretKey, KeyError> {
+        let config = Config::builder()
+            .add_source(File::with_name(&self.path))
+            .build()
+            .map_err(|e| KeyError::Io(e.to_string()))?;
+
+        let mut value = config.get_string(&self.name)
+            .map_err(|_| KeyError::NotFound(format!("{} in {}", self.name, self.path)))?;
+        let decoded = self.encoding.decode(value.as_bytes());
+        value.zeroize();
+        decoded.map(SecretKey::new)
+    }
+}
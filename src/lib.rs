@@ -1,19 +1,87 @@
+mod apps;
 mod message;
+mod nodes;
+mod perf;
 mod protocol;
 mod connection;
+mod console;
 mod config;
+mod developer;
+mod epg;
+mod error;
+mod http;
+mod image;
+mod key;
+mod lineup;
+mod macros;
+mod media;
+#[cfg(feature = "scripting")]
+mod script;
+mod setting;
 #[cfg(test)]
 mod tests;
+mod tracker;
+mod tv;
+mod ui;
+mod volume;
+mod wait;
+mod xml;
 
 // Public re-exports
+pub use apps::{App, IconCache, InstallReport};
+pub use config::{
+    DeviceProfile,
+    KeySource,
+    ProfileError,
+    Settings,
+    Timeouts,
+};
 pub use connection::Connection;
+pub use console::{ConsoleEvent, CrashDetector, CrashReport, DebugConsole, LogLine};
+pub use developer::{DeveloperClient, InstallerResult};
+pub use epg::{Program, ProgramGuide};
+pub use error::RequestError;
+pub use image::{Image, Screenshot};
+pub use key::{
+    ConfigKey,
+    EnvKey,
+    FileKey,
+    KeyEncoding,
+    KeyError,
+    KeyProvider,
+    LiteralKey,
+    SecretKey,
+};
+pub use lineup::{Lineup, LineupDiff, Renumbered};
+pub use macros::{
+    Macro,
+    MacroAbort,
+    MacroError,
+    MacroReport,
+    Step,
+    StepResult,
+};
+pub use media::{Buffering, MediaPlayer, PlayerState};
 pub use message::{
     ContentData,
     ContentType,
+    notification::Notification,
     request::Request,
     response::Response,
 };
+#[cfg(feature = "scripting")]
+pub use script::{ScriptEngine, ScriptError};
+pub use nodes::{NodeCountChange, NodeDiff, NodeStats};
+pub use perf::{ChannelPerf, PerfLog, PerfSample, SampleFormat};
 pub use protocol::{
+    auth::AuthOutcome,
     command::Set,
+    deeplink::{DeepLink, MediaType},
     query::Get,
-};
\ No newline at end of file
+};
+pub use setting::{AllowedValues, DeviceSetting, PictureSetting, ResetScope};
+pub use tracker::{PlaybackEvent, PlaybackStats, PlaybackTracker};
+pub use tv::{TvChannel, TvInput};
+pub use ui::{Bounds, Selector, UiNode};
+pub use volume::Volume;
+pub use wait::Condition;
\ No newline at end of file
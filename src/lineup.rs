@@ -0,0 +1,224 @@
+use std::path::Path;
+
+use crate::connection::Connection;
+use crate::error::RequestError;
+use crate::protocol::deeplink::encode_params;
+use crate::tv::{TvChannel, TvInput};
+
+/// Header line of exported CSV lineups
+const CSV_HEADER: &str = "number,name,type,physical_id,hidden,favorite";
+
+/// TV channel lineup which can be exported and compared against a saved copy
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Lineup {
+    pub channels:   Vec<TvChannel>,
+}
+
+/// Channel whose number changed between two lineups
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Renumbered {
+    pub name:       String,
+    pub before:     String,
+    pub after:      String,
+}
+
+/// Differences between a saved lineup and the current one
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LineupDiff {
+    pub added:      Vec<TvChannel>,
+    pub removed:    Vec<TvChannel>,
+    pub renumbered: Vec<Renumbered>,
+}
+
+impl LineupDiff {
+    /// Whether the lineups match
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.renumbered.is_empty()
+    }
+}
+
+impl Lineup {
+    /// Create a lineup from a channel list
+    pub fn new(channels: Vec<TvChannel>) -> Self {
+        Self { channels }
+    }
+
+    /// CSV with every channel field
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("{}\n", CSV_HEADER);
+        for channel in &self.channels {
+            let fields = [
+                csv_field(&channel.number),
+                csv_field(&channel.name),
+                csv_field(&channel.channel_type),
+                csv_field(channel.physical_id.as_deref().unwrap_or_default()),
+                channel.hidden.to_string(),
+                channel.favorite.to_string(),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// M3U playlist with each entry pointing at the connection's device launch URL for the channel
+    pub fn to_m3u(&self, connection: &Connection) -> String {
+        let [a, b, c, d] = connection.ipv4;
+        let mut m3u = String::from("#EXTM3U\n");
+        for channel in &self.channels {
+            m3u.push_str(&format!(
+                "#EXTINF:-1 tvg-chno=\"{}\" tvg-name=\"{}\" group-title=\"{}\",{}\n",
+                m3u_attribute(&channel.number), m3u_attribute(&channel.name),
+                m3u_attribute(&channel.channel_type), channel.name
+            ));
+            let query = encode_params(&[(String::from("ch"), channel.number.clone())]);
+            m3u.push_str(&format!("http://{}.{}.{}.{}:{}/launch/{}?{}\n", a, b, c, d, connection.port, TvInput::Tuner.id(), query));
+        }
+        m3u
+    }
+
+    /// Parse a lineup exported as M3U or CSV
+    pub fn parse(content: &str) -> Result<Self, RequestError> {
+        if content.trim_start().starts_with("#EXTM3U") {
+            Ok(Self::new(parse_m3u(content)))
+        }
+        else {
+            parse_csv(content).map(Self::new)
+        }
+    }
+
+    /// Read a saved lineup from a file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RequestError> {
+        let content = std::fs::read_to_string(path).map_err(|e| RequestError::Io(e.to_string()))?;
+        Self::parse(&content)
+    }
+
+    /// Compare this saved lineup against the current one; channels are matched by name,
+    /// so a name found in both with a different number counts as renumbered
+    pub fn compare(&self, current: &Lineup) -> LineupDiff {
+        // Channels unchanged in both lineups take no further part
+        let mut saved: Vec<&TvChannel> = self.channels.iter()
+            .filter(|channel| !current.channels.iter().any(|other| same_channel(channel, other)))
+            .collect();
+        let changed = current.channels.iter()
+            .filter(|channel| !self.channels.iter().any(|other| same_channel(channel, other)));
+
+        let mut diff = LineupDiff::default();
+        for channel in changed {
+            match saved.iter().position(|other| other.name == channel.name) {
+                Some(index) => {
+                    let before = saved.remove(index);
+                    diff.renumbered.push(Renumbered {
+                        name: channel.name.clone(),
+                        before: before.number.clone(),
+                        after: channel.number.clone(),
+                    });
+                }
+                None => diff.added.push(channel.clone()),
+            }
+        }
+        diff.removed = saved.into_iter().cloned().collect();
+        diff
+    }
+}
+
+/// Whether two entries are the same channel on the same number
+fn same_channel(a: &TvChannel, b: &TvChannel) -> bool {
+    a.number == b.number && a.name == b.name
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+    else {
+        String::from(value)
+    }
+}
+
+/// Split one CSV line into fields, handling quoted fields
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Parse CSV written by `Lineup::to_csv`
+fn parse_csv(content: &str) -> Result<Vec<TvChannel>, RequestError> {
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    match lines.next() {
+        Some(header) if header.trim() == CSV_HEADER => {}
+        _ => return Err(RequestError::Content(String::from("not a channel lineup CSV"))),
+    }
+
+    lines.enumerate()
+        .map(|(index, line)| {
+            let fields = csv_fields(line);
+            if fields.len() != 6 {
+                return Err(RequestError::Content(format!("lineup row {} has {} fields", index + 1, fields.len())));
+            }
+            Ok(TvChannel {
+                number: fields[0].clone(),
+                name: fields[1].clone(),
+                channel_type: fields[2].clone(),
+                physical_id: Some(fields[3].clone()).filter(|id| !id.is_empty()),
+                hidden: fields[4] == "true",
+                favorite: fields[5] == "true",
+            })
+        })
+        .collect()
+}
+
+/// Escape a value for a double-quoted M3U attribute
+fn m3u_attribute(value: &str) -> String {
+    value.replace('"', "'")
+}
+
+/// Value of a double-quoted attribute in an EXTINF line
+fn extinf_attribute(line: &str, name: &str) -> Option<String> {
+    let start = line.find(&format!("{}=\"", name))? + name.len() + 2;
+    let end = line[start..].find('"')? + start;
+    Some(String::from(&line[start..end]))
+}
+
+/// Parse an M3U playlist written by `Lineup::to_m3u`
+fn parse_m3u(content: &str) -> Vec<TvChannel> {
+    content.lines()
+        .filter_map(|line| line.trim().strip_prefix("#EXTINF:"))
+        .map(|info| {
+            // The title follows the first comma outside the quoted attributes
+            let mut quoted = false;
+            let title_start = info.char_indices()
+                .find(|(_, c)| {
+                    if *c == '"' {
+                        quoted = !quoted;
+                    }
+                    *c == ',' && !quoted
+                })
+                .map(|(index, _)| index + 1)
+                .unwrap_or(info.len());
+
+            TvChannel {
+                number: extinf_attribute(info, "tvg-chno").unwrap_or_default(),
+                name: extinf_attribute(info, "tvg-name").unwrap_or_else(|| String::from(info[title_start..].trim())),
+                channel_type: extinf_attribute(info, "group-title").unwrap_or_default(),
+                ..TvChannel::default()
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,212 @@
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::error::RequestError;
+
+/// Single step of a key macro
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Step {
+    Press { key: String },
+    Wait { duration: Duration },
+    Type { text: String },
+}
+
+/// Sequence of key presses, waits and typed text
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Macro {
+    pub steps: Vec<Step>,
+}
+
+/// Failure to parse a macro, with the index of the offending step
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MacroError {
+    pub position:   usize,
+    pub message:    String,
+}
+
+impl fmt::Display for MacroError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid macro step {}: {}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for MacroError {}
+
+impl Macro {
+    /// Parse a comma separated macro, e.g. `Home, wait 2s, Down x3, Select, type "news"`
+    pub fn parse(text: &str) -> Result<Self, MacroError> {
+        let mut steps = vec![];
+        for (position, token) in split_steps(text).iter().enumerate() {
+            steps.append(&mut parse_step(token).map_err(|message| MacroError { position, message })?);
+        }
+        Ok(Self { steps })
+    }
+
+    /// Parse a JSON array of steps, each either a step string or an object such as
+    /// `{"press": "Down", "repeat": 3}`, `{"wait_ms": 2000}` or `{"type": "news"}`
+    pub fn from_json(json: &str) -> Result<Self, MacroError> {
+        let value = serde_json::from_str::<Value>(json)
+            .map_err(|e| MacroError { position: 0, message: e.to_string() })?;
+        let items = value.as_array()
+            .ok_or_else(|| MacroError { position: 0, message: String::from("expected an array of steps") })?;
+
+        let mut steps = vec![];
+        for (position, item) in items.iter().enumerate() {
+            let mut parsed = match item {
+                Value::String(token) => parse_step(token),
+                Value::Object(map) => {
+                    if let Some(Value::String(key)) = map.get("press") {
+                        let repeat = map.get("repeat").and_then(Value::as_u64).unwrap_or(1);
+                        Ok((0..repeat).map(|_| Step::Press { key: key.clone() }).collect())
+                    }
+                    else if let Some(millis) = map.get("wait_ms").and_then(Value::as_u64) {
+                        Ok(vec![Step::Wait { duration: Duration::from_millis(millis) }])
+                    }
+                    else if let Some(Value::String(wait)) = map.get("wait") {
+                        parse_duration(wait).map(|duration| vec![Step::Wait { duration }])
+                    }
+                    else if let Some(Value::String(text)) = map.get("type") {
+                        Ok(vec![Step::Type { text: text.clone() }])
+                    }
+                    else {
+                        Err(String::from("unrecognized step object"))
+                    }
+                }
+                _ => Err(String::from("expected a string or object")),
+            }.map_err(|message| MacroError { position, message })?;
+            steps.append(&mut parsed);
+        }
+        Ok(Self { steps })
+    }
+}
+
+/// Split macro text on commas outside of quotes
+fn split_steps(text: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ',' | '\n' if !quoted => {
+                tokens.push(current.trim().to_string());
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    tokens.push(current.trim().to_string());
+    tokens.into_iter().filter(|token| !token.is_empty()).collect()
+}
+
+/// Parse one step token, expanding repeats
+fn parse_step(token: &str) -> Result<Vec<Step>, String> {
+    let token = token.trim();
+    let (word, rest) = match token.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (token, ""),
+    };
+
+    match word.to_lowercase().as_str() {
+        "wait" => parse_duration(rest).map(|duration| vec![Step::Wait { duration }]),
+        "type" => {
+            let text = rest.strip_prefix('"').and_then(|rest| rest.strip_suffix('"'))
+                .ok_or_else(|| format!("expected quoted text after type: {}", token))?;
+            Ok(vec![Step::Type { text: String::from(text) }])
+        }
+        _ if word.is_empty() => Err(String::from("empty step")),
+        _ => {
+            let repeat = match rest {
+                "" => 1,
+                _ => rest.strip_prefix('x').or_else(|| rest.strip_prefix('X'))
+                    .and_then(|count| count.trim().parse::<usize>().ok())
+                    .ok_or_else(|| format!("expected repeat count like x3: {}", token))?,
+            };
+            Ok((0..repeat).map(|_| Step::Press { key: String::from(word) }).collect())
+        }
+    }
+}
+
+/// Parse a duration such as 500ms, 2s or 1.5s
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let invalid = || format!("invalid duration: {}", text);
+    if let Some(millis) = text.strip_suffix("ms") {
+        millis.trim().parse::<u64>().map(Duration::from_millis).map_err(|_| invalid())
+    }
+    else if let Some(seconds) = text.strip_suffix('s') {
+        seconds.trim().parse::<f64>().ok()
+            .filter(|seconds| *seconds >= 0.0)
+            .map(Duration::from_secs_f64)
+            .ok_or_else(invalid)
+    }
+    else {
+        Err(invalid())
+    }
+}
+
+/// Key names which type the given text, one literal key per character
+pub fn literal_keys(text: &str) -> Vec<String> {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                format!("Lit_{}", c)
+            }
+            else {
+                let mut buffer = [0; 4];
+                let encoded: String = c.encode_utf8(&mut buffer).bytes().map(|b| format!("%{:02X}", b)).collect();
+                format!("Lit_{}", encoded)
+            }
+        })
+        .collect()
+}
+
+/// Handle used to stop a running macro between steps, during waits or between typed characters
+#[derive(Clone, Debug, Default)]
+pub struct MacroAbort {
+    flag: Arc<AtomicBool>,
+}
+
+impl MacroAbort {
+    /// Create a new handle
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the macro runner to stop
+    pub fn abort(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether or not an abort was requested
+    pub fn is_aborted(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+}
+
+/// Result of a single executed step
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StepResult {
+    pub step:       Step,
+    pub outcome:    Result<(), RequestError>,
+}
+
+/// Results of a macro run; steps after a failure or abort are not run
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MacroReport {
+    pub results:    Vec<StepResult>,
+    pub aborted:    bool,
+}
+
+impl MacroReport {
+    /// Whether every step ran and succeeded
+    pub fn is_success(&self) -> bool {
+        !self.aborted && self.results.iter().all(|result| result.outcome.is_ok())
+    }
+}
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use crate::error::RequestError;
+
+/// Media player state reported by the media-player query
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PlayerState {
+    Close,
+    Open,
+    Startup,
+    Buffer,
+    Play,
+    Pause,
+    Stop,
+    Finished,
+    None,
+    Unknown(String),
+}
+
+impl PlayerState {
+    /// Parse the state attribute
+    pub fn parse(state: &str) -> Self {
+        match state {
+            "close" => PlayerState::Close,
+            "open" => PlayerState::Open,
+            "startup" => PlayerState::Startup,
+            "buffer" => PlayerState::Buffer,
+            "play" => PlayerState::Play,
+            "pause" => PlayerState::Pause,
+            "stop" => PlayerState::Stop,
+            "finished" => PlayerState::Finished,
+            "none" | "" => PlayerState::None,
+            other => PlayerState::Unknown(String::from(other)),
+        }
+    }
+
+    /// Whether media is playing or buffering to play
+    pub fn is_playing(&self) -> bool {
+        matches!(self, PlayerState::Play | PlayerState::Buffer)
+    }
+
+    /// Whether playback has ended or no media is loaded
+    pub fn is_stopped(&self) -> bool {
+        matches!(self, PlayerState::Close | PlayerState::Stop | PlayerState::Finished | PlayerState::None)
+    }
+}
+
+/// Buffer fill levels
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Buffering {
+    pub current:    u64,
+    pub max:        u64,
+    pub target:     u64,
+}
+
+/// Parsed media player status
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MediaPlayer {
+    pub state:          PlayerState,
+    pub error:          bool,
+    pub plugin_id:      Option<String>,
+    pub plugin_name:    Option<String>,
+    pub position:       Option<Duration>,
+    pub duration:       Option<Duration>,
+    pub is_live:        bool,
+    pub buffering:      Option<Buffering>,
+}
+
+impl MediaPlayer {
+    /// Parse the media-player XML
+    pub fn parse(xml: &str) -> Result<Self, RequestError> {
+        let document = roxmltree::Document::parse(xml).map_err(|e| RequestError::Content(e.to_string()))?;
+        let player = document.descendants()
+            .find(|node| node.has_tag_name("player"))
+            .ok_or_else(|| RequestError::Content(String::from("missing player element")))?;
+
+        let child = |tag: &str| player.children().find(|node| node.has_tag_name(tag));
+        let text = |tag: &str| child(tag).and_then(|node| node.text()).map(|text| String::from(text.trim()));
+        let plugin = child("plugin");
+
+        let buffering = child("buffering").map(|node| {
+            let value = |name: &str| node.attribute(name).and_then(|value| value.parse::<u64>().ok()).unwrap_or(0);
+            Buffering { current: value("current"), max: value("max"), target: value("target") }
+        });
+
+        Ok(Self {
+            state: PlayerState::parse(player.attribute("state").unwrap_or_default()),
+            error: player.attribute("error") == Some("true"),
+            plugin_id: plugin.and_then(|node| node.attribute("id")).map(String::from),
+            plugin_name: plugin.and_then(|node| node.attribute("name")).map(String::from),
+            position: text("position").as_deref().and_then(parse_millis),
+            duration: text("duration").as_deref().and_then(parse_millis),
+            is_live: text("is_live").as_deref() == Some("true"),
+            buffering,
+        })
+    }
+}
+
+/// Parse a time such as "12345 ms"
+fn parse_millis(text: &str) -> Option<Duration> {
+    text.trim_end_matches("ms").trim().parse::<u64>().ok().map(Duration::from_millis)
+}
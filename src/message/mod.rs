@@ -1,3 +1,4 @@
+pub mod notification;
 pub mod request;
 pub mod response;
 
@@ -13,7 +14,7 @@ pub enum ContentData {
 }
 
 // Content type indicator
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ContentType {
     Jpeg,
     Json,
@@ -35,7 +36,7 @@ impl ECPMessage {
     // TODO: Implement request-id parsing to check we got the correct response
     /// Handle non-auth messages
     pub fn from_message(message: Message) -> ECPMessage {
-        return if message.is_close() || message.is_ping() || message.is_pong() {
+        if message.is_close() || message.is_ping() || message.is_pong() {
             ECPMessage::Control {
                 bytes: message.into_data(),
             }
@@ -46,7 +47,7 @@ impl ECPMessage {
             }
         }
         else if message.is_text() {
-            let text = String::from(message.into_text().unwrap());
+            let text = message.into_text().unwrap();
             if Self::is_auth_message(&text) {
                 println!("[!] Unexpected auth message received: {}", text);
                 ECPMessage::Authentication {
@@ -69,7 +70,7 @@ impl ECPMessage {
 
     /// Consume an ECPMessage and return a WebSocket Message
     pub fn into_message(self) -> Message {
-        return match self {
+        match self {
             ECPMessage::Authentication { text, .. } |
             ECPMessage::Text { text } => {
                 Message::Text(text)
@@ -83,14 +84,14 @@ impl ECPMessage {
     }
 
     /// Return the parsed message only if it is an authentication message
-    pub(crate) fn try_from_auth_message(message: Message, counter: i32, key: &Vec<u8>) -> Option<ECPMessage> {
-        return if message.is_text() {
-            let text = String::from(message.into_text().unwrap());
+    pub(crate) fn try_from_auth_message(message: Message, counter: i32, key: &[u8]) -> Option<ECPMessage> {
+        if message.is_text() {
+            let text = message.into_text().unwrap();
             if Self::is_auth_challenge(&text) {
                 let response = Self::generate_challenge_response(&text, counter, key);
                 Some(ECPMessage::Authentication {
                     text,
-                    response: Some(response),
+                    response,
                 })
             }
             else if Self::is_auth_message(&text) {
@@ -0,0 +1,38 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Event notification pushed by the device after subscribing with request-events
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Notification {
+    pub event:      String,
+    pub params:     HashMap<String, String>,
+}
+
+impl Notification {
+    /// Parse a notify frame, ignoring anything else
+    pub fn from_text(text: &str) -> Option<Self> {
+        let json = serde_json::from_str::<Value>(text).ok()?;
+        let event = String::from(json["notify"].as_str()?);
+
+        let params = json.as_object()?
+            .iter()
+            .filter(|(key, _)| key.as_str() != "notify")
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(string) => string.clone(),
+                    other => other.to_string(),
+                };
+                (key.clone(), value)
+            })
+            .collect();
+
+        Some(Self { event, params })
+    }
+
+    /// Get a param by name, with or without the "param-" prefix
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name)
+            .or_else(|| self.params.get(&format!("param-{}", name)))
+            .map(String::as_str)
+    }
+}
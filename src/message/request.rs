@@ -26,6 +26,11 @@ impl Request {
         self
     }
 
+    /// Get the request-id
+    pub fn request_id(&self) -> i32 {
+        self.request_id
+    }
+
     /// Set the request subject
     pub fn set_subject(mut self, subject: &str) -> Self {
         self.subject = String::from(subject);
@@ -63,6 +68,12 @@ impl Request {
     }
 }
 
+impl Default for Request {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl From<Get> for Request {
     fn from(get: Get) -> Self {
         Request::new()
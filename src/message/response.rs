@@ -27,7 +27,7 @@ impl Response {
                         Some(
                             Response {
                                 subject: String::new(),
-                                response_id: 0,
+                                response_id: -1,
                                 content_data: None,
                                 content_type: None,
                                 status_code: 0,
@@ -55,12 +55,7 @@ impl Response {
         };
 
         let response_id = match &json["response-id"] {
-            Value::String(text) => {
-                match text.parse::<i32>() {
-                    Ok(parsed) => parsed,
-                    Err(_) => -1,
-                }
-            },
+            Value::String(text) => text.parse::<i32>().unwrap_or(-1),
             _ => -1,
         };
 
@@ -114,12 +109,7 @@ impl Response {
         };
 
         let status_code = match &json["status"] {
-            Value::String(text) => {
-                match text.parse::<i32>() {
-                    Ok(parsed) => parsed,
-                    Err(_) => 0,
-                }
-            },
+            Value::String(text) => text.parse::<i32>().unwrap_or_default(),
             _ => 0,
         };
 
@@ -139,6 +129,14 @@ impl Response {
         }
     }
 
+    /// Text content of this response, if any
+    pub fn text(&self) -> Option<&str> {
+        match &self.content_data {
+            Some(ContentData::Text { string }) => Some(string),
+            _ => None,
+        }
+    }
+
     /// Whether or not this response has a success status code
     pub fn is_success(&self) -> bool {
         self.status_code == 200
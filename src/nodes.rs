@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use crate::error::RequestError;
+
+/// SceneGraph node statistics from the sgnodes query
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NodeStats {
+    pub total:      usize,
+    pub by_type:    BTreeMap<String, usize>,
+    pub roots:      usize,
+    pub orphans:    usize,
+}
+
+/// Change in a node type's count between two snapshots
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NodeCountChange {
+    pub node_type:  String,
+    pub before:     usize,
+    pub after:      usize,
+}
+
+impl NodeCountChange {
+    /// Signed difference in count
+    pub fn delta(&self) -> i64 {
+        self.after as i64 - self.before as i64
+    }
+}
+
+/// Differences between two node snapshots
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NodeDiff {
+    pub total_delta:    i64,
+    pub orphan_delta:   i64,
+    pub grown:          Vec<NodeCountChange>,
+    pub shrunk:         Vec<NodeCountChange>,
+}
+
+impl NodeStats {
+    /// Parse the sgnodes XML; top level nodes are roots, and roots other than a Scene are orphans
+    pub fn parse(xml: &str) -> Result<Self, RequestError> {
+        let document = roxmltree::Document::parse(xml).map_err(|e| RequestError::Content(e.to_string()))?;
+        let root = document.root_element();
+        let container = root.children()
+            .find(|node| node.has_tag_name("All_Nodes"))
+            .unwrap_or(root);
+
+        let mut stats = NodeStats::default();
+        for node in container.children().filter(|node| node.is_element()) {
+            stats.roots += 1;
+            if !node_type(&node).ends_with("Scene") {
+                stats.orphans += 1;
+            }
+
+            for descendant in node.descendants().filter(|node| node.is_element()) {
+                stats.total += 1;
+                *stats.by_type.entry(node_type(&descendant)).or_insert(0) += 1;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Compare against an earlier snapshot, largest growth first
+    pub fn diff(before: &NodeStats, after: &NodeStats) -> NodeDiff {
+        let mut grown = vec![];
+        let mut shrunk = vec![];
+
+        let mut types: Vec<&String> = before.by_type.keys().chain(after.by_type.keys()).collect();
+        types.sort();
+        types.dedup();
+        for node_type in types {
+            let change = NodeCountChange {
+                node_type: node_type.clone(),
+                before: before.by_type.get(node_type).copied().unwrap_or(0),
+                after: after.by_type.get(node_type).copied().unwrap_or(0),
+            };
+            match change.delta() {
+                delta if delta > 0 => grown.push(change),
+                delta if delta < 0 => shrunk.push(change),
+                _ => {}
+            }
+        }
+        grown.sort_by_key(|change| -change.delta());
+        shrunk.sort_by_key(|change| change.delta());
+
+        NodeDiff {
+            total_delta: after.total as i64 - before.total as i64,
+            orphan_delta: after.orphans as i64 - before.orphans as i64,
+            grown,
+            shrunk,
+        }
+    }
+}
+
+/// Node type from a type attribute, falling back to the element tag
+fn node_type(node: &roxmltree::Node) -> String {
+    String::from(node.attribute("type").or_else(|| node.attribute("nodeType")).unwrap_or(node.tag_name().name()))
+}
@@ -0,0 +1,151 @@
+use serde_json::json;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::RequestError;
+
+/// CPU and memory use of the running channel, from the chanperf query
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChannelPerf {
+    pub plugin_id:          String,
+    pub cpu_duration_ms:    u64,
+    pub cpu_user:           f64,
+    pub cpu_sys:            f64,
+    pub memory_used:        u64,
+    pub memory_res:         u64,
+    pub memory_anon:        u64,
+    pub memory_file:        u64,
+    pub memory_shared:      u64,
+    pub memory_swap:        u64,
+}
+
+impl ChannelPerf {
+    /// Parse the chanperf XML
+    pub fn parse(xml: &str) -> Result<Self, RequestError> {
+        let document = roxmltree::Document::parse(xml).map_err(|e| RequestError::Content(e.to_string()))?;
+
+        let status = document.descendants()
+            .find(|node| node.has_tag_name("status"))
+            .and_then(|node| node.text())
+            .unwrap_or("OK");
+        if status.trim() != "OK" {
+            let error = document.descendants()
+                .find(|node| node.has_tag_name("error"))
+                .and_then(|node| node.text())
+                .unwrap_or(status);
+            return Err(RequestError::Content(String::from(error.trim())));
+        }
+
+        let plugin = document.descendants()
+            .find(|node| node.has_tag_name("plugin"))
+            .ok_or_else(|| RequestError::Content(String::from("no running channel")))?;
+
+        // Value of a child element under a section such as cpu-percent or memory
+        let value = |section: &str, name: &str| -> Option<String> {
+            plugin.descendants()
+                .find(|node| node.has_tag_name(section))?
+                .children()
+                .find(|node| node.has_tag_name(name))?
+                .text()
+                .map(|text| String::from(text.trim()))
+        };
+        let number = |section: &str, name: &str| value(section, name).and_then(|text| text.parse::<u64>().ok()).unwrap_or(0);
+        let percent = |name: &str| value("cpu-percent", name).and_then(|text| text.parse::<f64>().ok()).unwrap_or(0.0);
+
+        Ok(Self {
+            plugin_id: String::from(plugin.attribute("id").unwrap_or_default()),
+            cpu_duration_ms: number("cpu-percent", "durationms"),
+            cpu_user: percent("user"),
+            cpu_sys: percent("sys"),
+            memory_used: number("memory", "used"),
+            memory_res: number("memory", "res"),
+            memory_anon: number("memory", "anon"),
+            memory_file: number("memory", "file"),
+            memory_shared: number("memory", "shared"),
+            memory_swap: number("memory", "swap"),
+        })
+    }
+}
+
+/// Channel performance reading with the time it was taken
+#[derive(Clone, Debug, PartialEq)]
+pub struct PerfSample {
+    pub timestamp:  SystemTime,
+    pub perf:       ChannelPerf,
+}
+
+impl PerfSample {
+    /// Milliseconds since the Unix epoch
+    pub fn unix_millis(&self) -> u128 {
+        self.timestamp.duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_millis()).unwrap_or(0)
+    }
+}
+
+/// Output format for performance samples
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SampleFormat {
+    Csv,
+    JsonLines,
+}
+
+impl SampleFormat {
+    /// Header line, if the format has one
+    pub fn header(&self) -> Option<&str> {
+        match self {
+            SampleFormat::Csv => Some("timestamp_ms,plugin_id,cpu_duration_ms,cpu_user,cpu_sys,memory_used,memory_res,memory_anon,memory_file,memory_shared,memory_swap"),
+            SampleFormat::JsonLines => None,
+        }
+    }
+
+    /// Format one sample as a line
+    pub fn line(&self, sample: &PerfSample) -> String {
+        let perf = &sample.perf;
+        match self {
+            SampleFormat::Csv => format!(
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                sample.unix_millis(), perf.plugin_id, perf.cpu_duration_ms, perf.cpu_user, perf.cpu_sys,
+                perf.memory_used, perf.memory_res, perf.memory_anon, perf.memory_file, perf.memory_shared, perf.memory_swap
+            ),
+            SampleFormat::JsonLines => json!({
+                "timestamp_ms": sample.unix_millis() as u64,
+                "plugin_id": perf.plugin_id,
+                "cpu_duration_ms": perf.cpu_duration_ms,
+                "cpu_user": perf.cpu_user,
+                "cpu_sys": perf.cpu_sys,
+                "memory_used": perf.memory_used,
+                "memory_res": perf.memory_res,
+                "memory_anon": perf.memory_anon,
+                "memory_file": perf.memory_file,
+                "memory_shared": perf.memory_shared,
+                "memory_swap": perf.memory_swap,
+            }).to_string(),
+        }
+    }
+}
+
+/// Writes samples as CSV or JSON lines, adding the header before the first sample
+pub struct PerfLog<W: Write> {
+    pub writer:     W,
+    pub format:     SampleFormat,
+    started:        bool,
+}
+
+impl<W: Write> PerfLog<W> {
+    /// Create a log writing in the given format
+    pub fn new(writer: W, format: SampleFormat) -> Self {
+        Self { writer, format, started: false }
+    }
+
+    /// Append a sample
+    pub fn write(&mut self, sample: &PerfSample) -> Result<(), RequestError> {
+        let io_error = |e: std::io::Error| RequestError::Io(e.to_string());
+        if !self.started {
+            if let Some(header) = self.format.header() {
+                writeln!(self.writer, "{}", header).map_err(io_error)?;
+            }
+            self.started = true;
+        }
+        writeln!(self.writer, "{}", self.format.line(sample)).map_err(io_error)?;
+        self.writer.flush().map_err(io_error)
+    }
+}
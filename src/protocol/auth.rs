@@ -1,19 +1,60 @@
 use base64;
+use serde_json::Value;
 use sha1::{Digest, Sha1};
 
 use tokio_tungstenite::{tungstenite::protocol::Message};
 use crate::message::ECPMessage;
 
-/// For a given auth challenge string, return the response
-fn gen_challenge_response(received_challenge: &str, key: &Vec<u8>) -> String {
-    let mut b64_auth_challenge_bytes = received_challenge.as_bytes().to_vec();
+/// Result of the authentication challenge-response flow
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AuthOutcome {
+    Success,
+    BadKey { status_code: i32, status_message: String },
+    UnexpectedFrame { text: String },
+    TimedOut,
+    ConnectFailed { reason: String },
+}
+
+impl AuthOutcome {
+    /// Whether or not authentication succeeded
+    pub fn is_success(&self) -> bool {
+        matches!(self, AuthOutcome::Success)
+    }
+
+    /// Determine the outcome from an authenticate response frame
+    pub fn from_response(content: &str) -> Self {
+        let json = match serde_json::from_str::<Value>(content) {
+            Ok(json) => json,
+            Err(_) => return AuthOutcome::UnexpectedFrame { text: String::from(content) },
+        };
+
+        let status_code = match &json["status"] {
+            Value::String(text) => text.parse::<i32>().unwrap_or(0),
+            Value::Number(number) => number.as_i64().unwrap_or(0) as i32,
+            _ => return AuthOutcome::UnexpectedFrame { text: String::from(content) },
+        };
+
+        let status_message = match &json["status-msg"] {
+            Value::String(text) => String::from(text),
+            _ => String::new(),
+        };
+
+        if status_code == 200 {
+            AuthOutcome::Success
+        }
+        else {
+            AuthOutcome::BadKey { status_code, status_message }
+        }
+    }
+}
 
-    let mut challenge_response_bytes = vec![];
-    challenge_response_bytes.append(&mut b64_auth_challenge_bytes);
-    challenge_response_bytes.append(&mut key.clone());
+/// For a given auth challenge string, return the response
+fn gen_challenge_response(received_challenge: &str, key: &[u8]) -> String {
+    let mut challenge_response_bytes = received_challenge.as_bytes().to_vec();
+    challenge_response_bytes.extend_from_slice(key);
 
     let hash = Sha1::digest(challenge_response_bytes);
-    base64::encode(&hash)
+    base64::encode(hash)
 }
 
 impl ECPMessage {
@@ -21,17 +62,35 @@ impl ECPMessage {
     pub fn is_auth_message(content: &str) -> bool { Self::is_auth_challenge(content) || Self::is_auth_response(content) }
 
     /// Is a challenge request
-    pub fn is_auth_challenge(content: &str) -> bool { content.contains(r#"{"notify":"authenticate""#) }
+    pub fn is_auth_challenge(content: &str) -> bool { Self::auth_frame_kind(content, "notify") }
 
     /// Is a challenge response result
-    pub fn is_auth_response(content: &str) -> bool { content.contains(r#"{"response":"authenticate""#) }
+    pub fn is_auth_response(content: &str) -> bool { Self::auth_frame_kind(content, "response") }
+
+    /// Whether the frame parses as JSON with the given field set to "authenticate"
+    fn auth_frame_kind(content: &str, field: &str) -> bool {
+        match serde_json::from_str::<Value>(content) {
+            Ok(json) => json[field] == "authenticate",
+            Err(_) => false,
+        }
+    }
+
+    /// Extract the challenge string from an authenticate notify frame
+    pub fn parse_challenge(message: &str) -> Option<String> {
+        match serde_json::from_str::<Value>(message) {
+            Ok(json) => match &json["param-challenge"] {
+                Value::String(challenge) => Some(String::from(challenge)),
+                _ => None,
+            },
+            Err(_) => None,
+        }
+    }
 
     /// Handle the authentication challenge request
-    pub fn generate_challenge_response(message: &str, counter: i32, key: &Vec<u8>) -> Message {
-        // Handle authentication request
-        let received_challenge = String::from_utf8(message.as_bytes()[44..68].to_vec()).unwrap();
+    pub fn generate_challenge_response(message: &str, counter: i32, key: &[u8]) -> Option<Message> {
+        let received_challenge = Self::parse_challenge(message)?;
         let challenge_response = gen_challenge_response(&received_challenge, key);
 
-        Message::text(format!("{{\"request\":\"authenticate\",\"request-id\":\"{}\",\"param-response\":\"{}\"}}", counter, challenge_response))
+        Some(Message::text(format!("{{\"request\":\"authenticate\",\"request-id\":\"{}\",\"param-response\":\"{}\"}}", counter, challenge_response)))
     }
-}
\ No newline at end of file
+}
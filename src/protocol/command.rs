@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use crate::protocol::deeplink::{DeepLink, encode_params};
+use crate::setting::ResetScope;
 
 #[allow(dead_code)]
 pub enum Set {
@@ -8,9 +10,15 @@ pub enum Set {
     },
     AudioSetting { id: String, value: String },
     CaptureScreen,
-    LaunchApp { channel_id: i32 },
+    Input { params: Vec<(String, String)> },
+    InstallApp { channel_id: i32 },
+    LaunchApp { channel_id: i32, deep_link: Option<DeepLink> },
+    LaunchTvInput { input_id: String, params: Vec<(String, String)> },
+    PictureSetting { id: String, value: String },
     PressKey { key: String },
-    ResetAudioSettings { scope: String },
+    RequestEvents { events: Vec<String> },
+    ResetAudioSettings { scope: ResetScope },
+    ResetPictureSettings { scope: ResetScope },
     ScreenSaver { channel_id: i32 },
     TexteditText {
         textedit_id: String, text: String,
@@ -26,9 +34,15 @@ impl Set {
             Set::AudioOutput { .. } => "set-audio-output",
             Set::AudioSetting { .. } => "set-audio-setting",
             Set::CaptureScreen => "capture-screen",
+            Set::Input { .. } => "input",
+            Set::InstallApp { .. } => "install",
             Set::LaunchApp { .. } => "launch",
+            Set::LaunchTvInput { .. } => "launch",
+            Set::PictureSetting { .. } => "set-pq-setting",
             Set::PressKey { .. } => "key-press",
+            Set::RequestEvents { .. } => "request-events",
             Set::ResetAudioSettings { .. } => "reset-audio-settings",
+            Set::ResetPictureSettings { .. } => "reset-pq-settings",
             Set::ScreenSaver { .. } => "set-screensaver",
             Set::TexteditText { .. } => "set-textedit-text"
         }
@@ -51,21 +65,44 @@ impl Set {
                 map.insert(String::from("param-app-build"), String::from(app_build));
                 Some(map)
             }
-            Set::AudioSetting { id, value } => {
+            Set::AudioSetting { id, value } | Set::PictureSetting { id, value } => {
                 map.insert(String::from("param-id"), String::from(id));
                 map.insert(String::from("param-value"), String::from(value));
                 Some(map)
             }
-            Set::LaunchApp { channel_id } => {
+            Set::Input { params } => {
+                map.insert(String::from("param-params"), encode_params(params));
+                Some(map)
+            }
+            Set::InstallApp { channel_id } => {
+                map.insert(String::from("param-channel-id"), format!("{}", channel_id));
+                Some(map)
+            }
+            Set::LaunchApp { channel_id, deep_link } => {
                 map.insert(String::from("param-channel-id"), format!("{}", channel_id));
+                if let Some(deep_link) = deep_link {
+                    map.insert(String::from("param-params"), encode_params(&deep_link.params()));
+                }
+                Some(map)
+            }
+            Set::LaunchTvInput { input_id, params } => {
+                map.insert(String::from("param-channel-id"), String::from(input_id));
+                if !params.is_empty() {
+                    map.insert(String::from("param-params"), encode_params(params));
+                }
                 Some(map)
             }
             Set::PressKey { key } => {
                 map.insert(String::from("param-key"), String::from(key));
                 Some(map)
             }
-            Set::ResetAudioSettings { scope } => {
-                map.insert(String::from("param-scope"), String::from(scope));
+            Set::RequestEvents { events } => {
+                let events: Vec<String> = events.iter().map(|event| format!("+{}", event)).collect();
+                map.insert(String::from("param-events"), events.join(","));
+                Some(map)
+            }
+            Set::ResetAudioSettings { scope } | Set::ResetPictureSettings { scope } => {
+                map.insert(String::from("param-scope"), String::from(scope.as_str()));
                 Some(map)
             }
             Set::ScreenSaver { channel_id } => {
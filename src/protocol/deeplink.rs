@@ -0,0 +1,81 @@
+/// Kind of content a deep link points to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MediaType {
+    Movie,
+    Episode,
+    Season,
+    Series,
+    ShortFormVideo,
+    Special,
+    TvSpecial,
+    Live,
+}
+
+impl MediaType {
+    /// Value of the mediaType launch param
+    pub fn as_str(&self) -> &str {
+        match self {
+            MediaType::Movie => "movie",
+            MediaType::Episode => "episode",
+            MediaType::Season => "season",
+            MediaType::Series => "series",
+            MediaType::ShortFormVideo => "shortFormVideo",
+            MediaType::Special => "special",
+            MediaType::TvSpecial => "tvSpecial",
+            MediaType::Live => "live",
+        }
+    }
+}
+
+/// Content to open when launching an app
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeepLink {
+    pub content_id:     String,
+    pub media_type:     MediaType,
+    pub extra:          Vec<(String, String)>,
+}
+
+impl DeepLink {
+    /// Create a deep link to the given content
+    pub fn new(content_id: &str, media_type: MediaType) -> Self {
+        Self {
+            content_id: String::from(content_id),
+            media_type,
+            extra: vec![],
+        }
+    }
+
+    /// Add an extra param passed through to the app
+    pub fn param(mut self, key: &str, value: &str) -> Self {
+        self.extra.push((String::from(key), String::from(value)));
+        self
+    }
+
+    /// All params in the order they are sent
+    pub fn params(&self) -> Vec<(String, String)> {
+        let mut params = vec![
+            (String::from("contentId"), self.content_id.clone()),
+            (String::from("mediaType"), String::from(self.media_type.as_str())),
+        ];
+        params.extend(self.extra.iter().cloned());
+        params
+    }
+}
+
+/// Percent-encode params into a query string for the param-params field
+pub fn encode_params(params: &[(String, String)]) -> String {
+    params.iter()
+        .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+/// Percent-encode everything except unreserved characters
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
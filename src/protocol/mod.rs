@@ -2,3 +2,4 @@ pub mod session;
 pub mod auth;
 pub mod query;
 pub mod command;
+pub mod deeplink;
@@ -5,14 +5,20 @@ pub enum Get {
     ActiveApp,
     ActiveTvChannel,
     ActiveTvInput,
+    AppUi,
     AudioDevice,
-    AudioSetting,
+    AudioSetting { id: String },
     AudioSettings,
     AvSyncOffset,
+    ChannelPerformance,
+    ColorSpaceSettings,
     DeviceInfo,
     InstalledApps,
     MediaPlayer,
+    PictureSetting { id: String },
+    PictureSettings,
     QueryAppIcon { channel_id: i32 },
+    SceneGraphNodes,
     Screensavers,
     TexteditState,
     Themes,
@@ -28,14 +34,20 @@ impl Get {
             Get::ActiveApp => "query-active-app",
             Get::ActiveTvChannel => "query-tv-active-channel",
             Get::ActiveTvInput => "query-tv-active-input",
+            Get::AppUi => "query-app-ui",
             Get::AudioDevice => "query-audio-device",
-            Get::AudioSetting => "query-audio-setting",
+            Get::AudioSetting { .. } => "query-audio-setting",
             Get::AudioSettings => "query-audio-settings",
             Get::AvSyncOffset => "query-av-sync-offset",
+            Get::ChannelPerformance => "query-chanperf",
+            Get::ColorSpaceSettings => "query-pq-color-space-settings",
             Get::DeviceInfo => "query-device-info",
             Get::InstalledApps => "query-apps",
             Get::MediaPlayer => "query-media-player",
+            Get::PictureSetting { .. } => "query-pq-setting",
+            Get::PictureSettings => "query-pq-settings",
             Get::QueryAppIcon { .. } => "query-icon",
+            Get::SceneGraphNodes => "query-sgnodes-all",
             Get::Screensavers => "query-screensavers",
             Get::TexteditState => "query-textedit-state",
             Get::Themes => "query-themes",
@@ -48,6 +60,11 @@ impl Get {
     /// Get any params this message might have
     pub fn params(&self) -> Option<HashMap<String, String>> {
         match self {
+            Get::AudioSetting { id } | Get::PictureSetting { id } => {
+                let mut map = HashMap::new();
+                map.insert(String::from("param-id"), String::from(id));
+                Some(map)
+            }
             Get::QueryAppIcon { channel_id } => {
                 let mut map = HashMap::new();
                 map.insert(String::from("param-channel-id"), format!("{}", channel_id));
@@ -10,6 +10,7 @@ use tokio_tungstenite::{
 };
 use tokio_tungstenite::tungstenite::handshake::client::Request;
 use crate::message::ECPMessage;
+use crate::protocol::auth::AuthOutcome;
 
 /// ECP WebSocket connection
 #[derive(Debug)]
@@ -20,60 +21,66 @@ pub struct ECPSocket {
 }
 
 impl ECPSocket {
-    /// Open unauthenticated connection to device
-    pub async fn open(ipv4: &str, port: &str) -> Self {
+    /// Open unauthenticated connection to device, or describe why it couldn't be opened
+    pub async fn open(ipv4: &str, port: &str, origin: &str) -> Result<Self, String> {
         // Open WebSocket connection
-        let websocket_stream = Self::connect_websocket(ipv4, port).await;
+        let websocket_stream = Self::connect_websocket(ipv4, port, origin).await?;
 
         // Separate sink & stream
         let (writer, reader) = websocket_stream.split();
 
-        Self {
+        Ok(Self {
             authenticated: false,
             writer,
             reader,
-        }
+        })
     }
 
     /// Perform authentication via challenge-response flow and return outcome, dropping all other messages
-    pub async fn authenticate(&mut self, key: &Vec<u8>, counter: i32) -> bool {
-        loop {
-            if let Some(Ok(message)) = self.reader.next().await {
-                if let Some(ecp_message) = ECPMessage::try_from_auth_message(message, counter, key) {
-                    match ecp_message {
-                        ECPMessage::Authentication { text, response } => {
-                            // Send reply and move on
-                            if let Some(reply) = response {
-                                let _ = self.writer.send(reply).await;
-                                continue;
-                            }
-
-                            if text.contains("Error") || text.contains("error") {
-                                println!("[!] Authentication error: {}", text);
-                                break;
-                            }
+    pub async fn authenticate(&mut self, key: &[u8], counter: i32) -> AuthOutcome {
+        let outcome = loop {
+            let message = match self.reader.next().await {
+                Some(Ok(message)) => message,
+                Some(Err(_)) => continue,
+                // Stream closed before authentication finished
+                None => break AuthOutcome::UnexpectedFrame { text: String::new() },
+            };
 
-                            // Check for success status code
-                            if text.contains("200") {
-                                self.authenticated = true;
-                                break;
-                            }
-
-                            println!("[-] Unexpected auth message received: {}", text);
+            if let Some(ECPMessage::Authentication { text, response }) = ECPMessage::try_from_auth_message(message, counter, key) {
+                if ECPMessage::is_auth_challenge(&text) {
+                    // Send reply and move on, unless the challenge couldn't be read
+                    match response {
+                        Some(reply) => {
+                            let _ = self.writer.send(reply).await;
+                            continue;
                         }
-                        _ => {}
+                        None => break AuthOutcome::UnexpectedFrame { text },
                     }
                 }
+
+                break AuthOutcome::from_response(&text);
+            }
+        };
+
+        match &outcome {
+            AuthOutcome::Success | AuthOutcome::TimedOut | AuthOutcome::ConnectFailed { .. } => {}
+            AuthOutcome::BadKey { status_code, status_message } => {
+                println!("[!] Authentication error: {} {}", status_code, status_message);
+            }
+            AuthOutcome::UnexpectedFrame { text } => {
+                println!("[-] Unexpected auth message received: {}", text);
             }
         }
-        self.authenticated
+
+        self.authenticated = outcome.is_success();
+        outcome
     }
 
-    /// Open WebSocket connection to device as an Android device
-    async fn connect_websocket(ipv4: &str, port: &str) -> WebSocketStream<MaybeTlsStream<TcpStream>> {
+    /// Open WebSocket connection to device with the given origin, e.g. as an Android device
+    async fn connect_websocket(ipv4: &str, port: &str, origin: &str) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, String> {
         // Generate random base-64 Sec-WebSocket-Key value
         let rand_bytes = thread_rng().gen::<[u8; 16]>();
-        let rand_websocket_key = base64::encode(rand_bytes.to_vec());
+        let rand_websocket_key = base64::encode(rand_bytes);
 
         // WebSocket upgrade request for /ecp-session with key, protocol, origin
         let request = Request::builder()
@@ -84,13 +91,13 @@ impl ECPSocket {
             .header("Sec-WebSocket-Key", &rand_websocket_key)
             .header("Sec-WebSocket-Version", "13")
             .header("Sec-WebSocket-Protocol", "ecp-2")
-            .header("Sec-WebSocket-Origin", "Android")
+            .header("Sec-WebSocket-Origin", origin)
             .uri(format!("ws://{}:{}/ecp-session", ipv4, port))
             .body(())
-            .unwrap();
+            .map_err(|e| e.to_string())?;
 
         // Connect and return stream
-        let (websocket_stream, _) = connect_async(request).await.unwrap();
-        websocket_stream
+        let (websocket_stream, _) = connect_async(request).await.map_err(|e| e.to_string())?;
+        Ok(websocket_stream)
     }
 }
\ No newline at end of file
@@ -0,0 +1,130 @@
+use rhai::{Dynamic, Engine, EvalAltResult};
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+use crate::connection::Connection;
+use crate::error::RequestError;
+use crate::message::request::Request;
+
+/// Delay between polls in `wait_for`
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Failure while compiling or running a script
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScriptError {
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "script error: {}", self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Rhai script engine with functions bound to a connection:
+///
+/// - `press(key)` presses a key
+/// - `type_text(text)` types text into the focused field
+/// - `launch(channel_id)` launches an app
+/// - `query(subject)` sends a query such as "query-active-app" and returns its content
+/// - `wait_for(subject, text, timeout_ms)` polls a query until its content contains the text
+/// - `screenshot(path)` saves a screen capture
+/// - `sleep(ms)` pauses the script
+///
+/// Scripts block on the connection, so they must be run from a multi-threaded tokio runtime;
+/// elsewhere the bound functions fail with a script error.
+pub struct ScriptEngine {
+    engine:         Engine,
+    connection:     Rc<RefCell<Connection>>,
+}
+
+/// Run a connection future to completion from a synchronous script function, failing
+/// outside a multi-threaded tokio runtime where blocking isn't possible
+fn block_on<F: Future>(future: F) -> Result<F::Output, Box<EvalAltResult>> {
+    let handle = Handle::try_current().map_err(|_| "scripts must be run inside a tokio runtime")?;
+    if handle.runtime_flavor() != RuntimeFlavor::MultiThread {
+        return Err("scripts must be run from a multi-threaded tokio runtime".into());
+    }
+    Ok(tokio::task::block_in_place(|| handle.block_on(future)))
+}
+
+/// Turn a request error into a script runtime error
+fn script_error(e: RequestError) -> Box<EvalAltResult> {
+    e.to_string().into()
+}
+
+/// Send a query by subject and return its text content
+fn query(connection: &Rc<RefCell<Connection>>, subject: &str) -> Result<String, Box<EvalAltResult>> {
+    let mut connection = connection.borrow_mut();
+    let response = block_on(connection.request(Request::new().set_subject(subject)))?.map_err(script_error)?;
+    Ok(String::from(response.text().unwrap_or_default()))
+}
+
+impl ScriptEngine {
+    /// Create a script engine bound to an opened connection
+    pub fn new(connection: Connection) -> Self {
+        let connection = Rc::new(RefCell::new(connection));
+        let mut engine = Engine::new();
+
+        let bound = connection.clone();
+        engine.register_fn("press", move |key: &str| -> Result<(), Box<EvalAltResult>> {
+            block_on(bound.borrow_mut().press_key(key))?.map_err(script_error)
+        });
+
+        let bound = connection.clone();
+        engine.register_fn("type_text", move |text: &str| -> Result<(), Box<EvalAltResult>> {
+            block_on(bound.borrow_mut().type_text(text))?.map_err(script_error)
+        });
+
+        let bound = connection.clone();
+        engine.register_fn("launch", move |channel_id: i64| -> Result<(), Box<EvalAltResult>> {
+            block_on(bound.borrow_mut().launch(channel_id as i32, None))?.map_err(script_error)
+        });
+
+        let bound = connection.clone();
+        engine.register_fn("query", move |subject: &str| query(&bound, subject));
+
+        let bound = connection.clone();
+        engine.register_fn("wait_for", move |subject: &str, text: &str, timeout_ms: i64| -> Result<bool, Box<EvalAltResult>> {
+            let deadline = Instant::now() + Duration::from_millis(timeout_ms.max(0) as u64);
+            loop {
+                if query(&bound, subject)?.contains(text) {
+                    return Ok(true);
+                }
+                if Instant::now() >= deadline {
+                    return Ok(false);
+                }
+                block_on(async { tokio::time::sleep(POLL_INTERVAL).await })?;
+            }
+        });
+
+        let bound = connection.clone();
+        engine.register_fn("screenshot", move |path: &str| -> Result<(), Box<EvalAltResult>> {
+            let screenshot = block_on(bound.borrow_mut().capture_screen())?.map_err(script_error)?;
+            screenshot.save(path).map_err(script_error)
+        });
+
+        engine.register_fn("sleep", |ms: i64| -> Result<(), Box<EvalAltResult>> {
+            // The sleep is created inside the runtime, so it is wrapped in a lazy future
+            block_on(async move { tokio::time::sleep(Duration::from_millis(ms.max(0) as u64)).await })
+        });
+
+        Self { engine, connection }
+    }
+
+    /// Run a script and return the value of its last expression
+    pub fn run(&self, script: &str) -> Result<Dynamic, ScriptError> {
+        self.engine.eval::<Dynamic>(script).map_err(|e| ScriptError { message: e.to_string() })
+    }
+
+    /// Borrow the underlying connection between script runs
+    pub fn connection(&self) -> std::cell::RefMut<'_, Connection> {
+        self.connection.borrow_mut()
+    }
+}
@@ -0,0 +1,149 @@
+use crate::error::RequestError;
+
+/// Values a device setting accepts
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AllowedValues {
+    Any,
+    List(Vec<String>),
+    Range { min: i64, max: i64, step: i64 },
+}
+
+/// Device setting with its current value, from a settings query
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeviceSetting {
+    pub id:         String,
+    pub value:      String,
+    pub allowed:    AllowedValues,
+}
+
+/// Picture quality setting
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum PictureSetting {
+    PictureMode,
+    Brightness,
+    Contrast,
+    ColorSpace,
+    ColorTemperature,
+    HdrMode,
+}
+
+impl PictureSetting {
+    /// Setting id used in picture quality queries and commands
+    pub fn id(&self) -> &str {
+        match self {
+            PictureSetting::PictureMode => "picture-mode",
+            PictureSetting::Brightness => "brightness",
+            PictureSetting::Contrast => "contrast",
+            PictureSetting::ColorSpace => "color-space",
+            PictureSetting::ColorTemperature => "color-temperature",
+            PictureSetting::HdrMode => "hdr-mode",
+        }
+    }
+}
+
+/// Scope of a reset-audio-settings or reset-pq-settings command
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ResetScope {
+    All,
+    CurrentInput,
+    CurrentMode,
+}
+
+impl ResetScope {
+    /// Value of the scope param
+    pub fn as_str(&self) -> &str {
+        match self {
+            ResetScope::All => "all",
+            ResetScope::CurrentInput => "current-input",
+            ResetScope::CurrentMode => "current-mode",
+        }
+    }
+}
+
+impl DeviceSetting {
+    /// Parse every setting in a settings XML; each setting is a "setting" element, or any element
+    /// with an id, giving its fields as attributes or child elements
+    pub fn parse_list(xml: &str) -> Result<Vec<DeviceSetting>, RequestError> {
+        let document = roxmltree::Document::parse(xml).map_err(|e| RequestError::Content(e.to_string()))?;
+        let mut elements: Vec<roxmltree::Node> = document.descendants()
+            .filter(|node| node.has_tag_name("setting"))
+            .collect();
+        if elements.is_empty() {
+            elements = document.descendants()
+                .filter(|node| node.is_element() && node.attribute("id").is_some())
+                .collect();
+        }
+
+        elements.iter()
+            .map(|element| {
+                let id = field(element, "id")
+                    .or_else(|| field(element, "name"))
+                    .ok_or_else(|| RequestError::Content(String::from("setting without an id")))?;
+                Ok(DeviceSetting {
+                    id,
+                    value: field(element, "value").or_else(|| field(element, "current-value")).unwrap_or_default(),
+                    allowed: allowed_values(element),
+                })
+            })
+            .collect()
+    }
+
+    /// Parse a single setting
+    pub fn parse(xml: &str) -> Result<DeviceSetting, RequestError> {
+        Self::parse_list(xml)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| RequestError::Content(String::from("missing setting")))
+    }
+
+    /// Check a value against the allowed values before sending it
+    pub fn check(&self, value: &str) -> Result<(), RequestError> {
+        let allowed = match &self.allowed {
+            AllowedValues::Any => true,
+            AllowedValues::List(values) => values.iter().any(|allowed| allowed == value),
+            AllowedValues::Range { min, max, step } => match value.parse::<i64>() {
+                Ok(number) => number >= *min && number <= *max && (*step <= 1 || (number - min) % step == 0),
+                Err(_) => false,
+            },
+        };
+
+        if allowed {
+            Ok(())
+        }
+        else {
+            Err(RequestError::InvalidSetting { id: self.id.clone(), value: String::from(value) })
+        }
+    }
+}
+
+/// Attribute or child element text
+fn field(element: &roxmltree::Node, name: &str) -> Option<String> {
+    element.attribute(name)
+        .or_else(|| element.children().find(|node| node.has_tag_name(name)).and_then(|node| node.text()))
+        .map(|text| String::from(text.trim()))
+        .filter(|text| !text.is_empty())
+}
+
+/// Allowed values from a min/max range, a comma separated values list, or option elements
+fn allowed_values(element: &roxmltree::Node) -> AllowedValues {
+    let number = |name: &str| field(element, name).and_then(|value| value.parse::<i64>().ok());
+    if let (Some(min), Some(max)) = (number("min"), number("max")) {
+        return AllowedValues::Range { min, max, step: number("step").unwrap_or(1) };
+    }
+
+    if let Some(values) = field(element, "values").or_else(|| field(element, "options")) {
+        return AllowedValues::List(values.split(',').map(|value| String::from(value.trim())).collect());
+    }
+
+    let options: Vec<String> = element.descendants()
+        .filter(|node| node.has_tag_name("option"))
+        .filter_map(|node| node.attribute("value").or_else(|| node.text()))
+        .map(|value| String::from(value.trim()))
+        .collect();
+    if options.is_empty() {
+        AllowedValues::Any
+    }
+    else {
+        AllowedValues::List(options)
+    }
+}
@@ -1,20 +1,113 @@
-use crate::connection::Connection;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
 
-use crate::config;
+use crate::apps::{App, IconCache};
+use crate::config::{KeySource, ProfileError, Settings};
+use crate::connection::Connection;
+use crate::console::{ConsoleEvent, DebugConsole};
+use crate::developer::{DeveloperClient, InstallerResult};
+use crate::epg::{Program, ProgramGuide};
+use crate::error::RequestError;
+use crate::http::{digest_response, parse_digest_challenge};
+use crate::image::{Image, Screenshot};
+use crate::key::{ConfigKey, EnvKey, FileKey, KeyEncoding, KeyError, KeyProvider, LiteralKey, SecretKey};
+use crate::lineup::{Lineup, Renumbered};
+use crate::macros::{literal_keys, Macro, MacroAbort, Step};
+use crate::media::{Buffering, MediaPlayer, PlayerState};
+use crate::message::notification::Notification;
 use crate::message::request::Request;
+use crate::message::response::Response;
+use crate::message::{ContentType, ECPMessage};
+use crate::nodes::NodeStats;
+use crate::perf::{ChannelPerf, PerfLog, PerfSample, SampleFormat};
+use crate::protocol::auth::AuthOutcome;
 use crate::protocol::command::Set;
+use crate::protocol::deeplink::{DeepLink, MediaType};
 use crate::protocol::query::Get;
+use crate::setting::{AllowedValues, DeviceSetting, PictureSetting, ResetScope};
+use crate::tracker::{PlaybackEvent, PlaybackTracker};
+use crate::tv::{TvChannel, TvInput};
+use crate::ui::{direction_toward, Bounds, Selector, UiNode};
+use crate::volume::Volume;
+use crate::wait::Condition;
 
 /// IPv4 for a device on your network
 const DEVICE_IP: [u8; 4] = [192, 168, 1, 226];
 
 #[allow(dead_code)]
-fn behold() -> Vec<u8> {
-    let config = config::load_from_file("conf/secrets");
-    assert!(config.len() > 0);
-    assert!(config.contains_key("this_one_shows_spirit"));
-    let key = config.get("this_one_shows_spirit").unwrap().as_bytes().to_vec();
-    key
+fn behold() -> SecretKey {
+    let provider = ConfigKey {
+        path: String::from("conf/secrets"),
+        name: String::from("this_one_shows_spirit"),
+        encoding: KeyEncoding::Raw,
+    };
+    provider.key().unwrap()
+}
+
+/// Local stand-in for a device's ECP session which accepts any key and answers each request with
+/// the frames `reply` returns, sending an empty frame as a ping and a binary frame; the server task
+/// yields every request once the connection is dropped
+async fn ecp_stand_in(
+    mut reply: impl FnMut(&Value) -> Vec<String> + Send + 'static
+) -> (Connection, JoinHandle<Vec<Value>>) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let challenge = r#"{"notify":"authenticate","param-challenge":"jEA0A1fEvQfAbZrj3vOMaQ==","timestamp":"1.234"}"#;
+        socket.send(Message::text(challenge)).await.unwrap();
+
+        let mut requests = vec![];
+        while let Some(Ok(message)) = socket.next().await {
+            let request = match message.into_text().map(|text| serde_json::from_str::<Value>(&text)) {
+                Ok(Ok(request)) => request,
+                _ => continue,
+            };
+            let frames = if request["request"] == "authenticate" {
+                vec![stand_in_reply(&request, None)]
+            }
+            else {
+                let frames = reply(&request);
+                requests.push(request);
+                frames
+            };
+            for frame in frames {
+                if frame.is_empty() {
+                    socket.send(Message::Ping(vec![1])).await.unwrap();
+                    socket.send(Message::Binary(vec![2])).await.unwrap();
+                }
+                else {
+                    socket.send(Message::text(frame)).await.unwrap();
+                }
+            }
+        }
+        requests
+    });
+
+    let mut connection = Connection::new([127, 0, 0, 1], b"key".to_vec());
+    connection.port = port as usize;
+    assert_eq!(connection.open().await, AuthOutcome::Success);
+    (connection, server)
+}
+
+/// Successful reply to a stand-in request, with optional XML content
+fn stand_in_reply(request: &Value, xml: Option<&str>) -> String {
+    let mut reply = json!({
+        "response": request["request"],
+        "response-id": request["request-id"],
+        "status": "200",
+        "status-msg": "OK",
+    });
+    if let Some(xml) = xml {
+        reply["content-type"] = json!("text/xml; charset=\"utf-8\"");
+        reply["content-data"] = json!(base64::encode(xml));
+    }
+    reply.to_string()
 }
 
 #[tokio::test]
@@ -24,7 +117,7 @@ async fn open_ecp_connection() {
         DEVICE_IP,
         key
     );
-    connection.open().await;
+    assert_eq!(connection.open().await, AuthOutcome::Success);
 
     assert!(connection.is_open());
     assert!(connection.is_authenticated());
@@ -124,7 +217,7 @@ async fn query_pq_options() {
     assert!(connection.is_authenticated());
 
     let pq_options = Request::new()
-        .set_subject("query-pq-color-space-settings")
+        .set_subject(Get::ColorSpaceSettings.subject())
         .set_request_id(connection.next_sync_number());
     let response = connection.send_request(pq_options).await;
     assert_ne!(response, None);
@@ -192,14 +285,1395 @@ async fn get_app_icon() {
     assert!(connection.is_open());
     assert!(connection.is_authenticated());
 
-    let request = Get::QueryAppIcon { channel_id: 140704 };
-    let response = connection.send_request(request.into()).await;
-    assert_ne!(response, None);
-    match response {
-        None => {}
-        Some(response) => {
-            println!("Received: {:?}", response.raw_bytes);
+    let icon = connection.app_icon(140704).await;
+    assert!(icon.is_ok());
+    let icon = icon.unwrap();
+    println!("Received {:?} icon: {:?}x{:?}", icon.format, icon.width, icon.height);
+}
+
+#[test]
+fn parse_auth_challenge() {
+    let notify = r#"{"notify":"authenticate","param-challenge":"jEA0A1fEvQfAbZrj3vOMaQ==","timestamp":"1.234"}"#;
+    assert!(ECPMessage::is_auth_challenge(notify));
+    assert!(!ECPMessage::is_auth_response(notify));
+    assert_eq!(ECPMessage::parse_challenge(notify), Some(String::from("jEA0A1fEvQfAbZrj3vOMaQ==")));
+
+    let reply = ECPMessage::generate_challenge_response(notify, 0, b"key");
+    assert!(reply.is_some());
+    assert!(reply.unwrap().into_text().unwrap().contains(r#""request-id":"0""#));
+
+    let missing = r#"{"notify":"authenticate","timestamp":"1.234"}"#;
+    assert_eq!(ECPMessage::parse_challenge(missing), None);
+    assert_eq!(ECPMessage::generate_challenge_response(missing, 0, b"key"), None);
+}
+
+#[test]
+fn parse_auth_outcome() {
+    let success = r#"{"response":"authenticate","response-id":"0","status":"200","status-msg":"OK"}"#;
+    assert_eq!(AuthOutcome::from_response(success), AuthOutcome::Success);
+
+    let bad_key = r#"{"response":"authenticate","response-id":"0","status":"401","status-msg":"Unauthorized"}"#;
+    assert_eq!(
+        AuthOutcome::from_response(bad_key),
+        AuthOutcome::BadKey { status_code: 401, status_message: String::from("Unauthorized") }
+    );
+
+    let garbage = r#"{"response":"authenticate""#;
+    assert_eq!(
+        AuthOutcome::from_response(garbage),
+        AuthOutcome::UnexpectedFrame { text: String::from(garbage) }
+    );
+}
+
+#[tokio::test]
+async fn open_refused_connection() {
+    // Free a local port so nothing is listening on it
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+
+    let mut connection = Connection::new([127, 0, 0, 1], b"key".to_vec());
+    connection.port = port as usize;
+    assert!(matches!(connection.open().await, AuthOutcome::ConnectFailed { .. }));
+    assert!(!connection.is_open());
+}
+
+#[test]
+fn key_providers() {
+    let literal = LiteralKey::new(b"secret".to_vec());
+    assert_eq!(literal.key().unwrap().expose(), b"secret");
+    assert_eq!(format!("{:?}", literal.key), "SecretKey(<redacted>)");
+
+    std::env::set_var("ECP_TEST_KEY_HEX", "736563726574");
+    let env = EnvKey { var: String::from("ECP_TEST_KEY_HEX"), encoding: KeyEncoding::Hex };
+    assert_eq!(env.key().unwrap().expose(), b"secret");
+
+    let missing = EnvKey { var: String::from("ECP_TEST_KEY_MISSING"), encoding: KeyEncoding::Raw };
+    assert!(matches!(missing.key(), Err(KeyError::NotFound(_))));
+
+    let path = std::env::temp_dir().join("ecp_test_key_b64");
+    std::fs::write(&path, "c2VjcmV0\n").unwrap();
+    let file = FileKey { path: path.clone(), encoding: KeyEncoding::Base64 };
+    assert_eq!(file.key().unwrap().expose(), b"secret");
+    let raw = FileKey { path: path.clone(), encoding: KeyEncoding::Raw };
+    assert_eq!(raw.key().unwrap().expose(), b"c2VjcmV0\n");
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn key_redacted_from_debug() {
+    let connection = Connection::new(DEVICE_IP, b"hunter2".to_vec());
+    let debug = format!("{:?}", connection);
+    assert!(!debug.contains("hunter2"));
+    assert!(!debug.contains("104, 117, 110"));
+    assert!(debug.contains("<redacted>"));
+}
+
+#[test]
+fn parse_device_profiles() {
+    let toml = r#"
+        [devices.living-room]
+        address = "192.168.1.226"
+        key = { source = "literal", value = "hunter2" }
+        groups = ["downstairs"]
+
+        [devices.bedroom]
+        address = "192.168.1.227"
+        port = 8061
+        origin = "iOS"
+        key = { source = "env", var = "ECP_BEDROOM_KEY", encoding = "hex" }
+        timeouts = { connect_ms = 2000, request_ms = 500 }
+        groups = ["upstairs", "kids"]
+    "#;
+    let settings = Settings::parse(toml, ::config::FileFormat::Toml).unwrap();
+
+    assert_eq!(settings.devices.len(), 2);
+    assert_eq!(settings.group("upstairs"), vec!["bedroom"]);
+    assert!(matches!(settings.device("garage"), Err(ProfileError::UnknownDevice(_))));
+
+    let living_room = settings.device("living-room").unwrap();
+    assert_eq!(living_room.port, 8060);
+    assert_eq!(living_room.origin, "Android");
+    assert!(!format!("{:?}", living_room).contains("hunter2"));
+
+    let connection = Connection::from_device_profile(living_room).unwrap();
+    assert_eq!(connection.ipv4, DEVICE_IP);
+    assert_eq!(connection.key.expose(), b"hunter2");
+    assert_eq!(connection.request_timeout, None);
+
+    let bedroom = settings.device("bedroom").unwrap();
+    assert!(matches!(bedroom.key, KeySource::Env { encoding: KeyEncoding::Hex, .. }));
+    assert_eq!(bedroom.timeouts.request_ms, Some(500));
+    assert!(matches!(Connection::from_device_profile(bedroom), Err(ProfileError::Key(KeyError::NotFound(_)))));
+}
+
+/// Minimal PNG header for a 1920x1080 image
+fn png_header() -> Vec<u8> {
+    let mut bytes = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+    bytes.extend_from_slice(&1920u32.to_be_bytes());
+    bytes.extend_from_slice(&1080u32.to_be_bytes());
+    bytes.extend_from_slice(&[8, 6, 0, 0, 0]);
+    bytes
+}
+
+#[test]
+fn read_image_dimensions() {
+    assert_eq!(Image::read_dimensions(&png_header()), Some((1920, 1080)));
+
+    // SOI, APP0 with 2 bytes of payload, SOF0 for 1280x720
+    let jpeg = vec![
+        0xFF, 0xD8,
+        0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00,
+        0xFF, 0xC0, 0x00, 0x11, 0x08, 0x02, 0xD0, 0x05, 0x00, 0x03,
+    ];
+    assert_eq!(Image::read_dimensions(&jpeg), Some((1280, 720)));
+    assert_eq!(Image::read_dimensions(b"not an image"), None);
+}
+
+#[test]
+fn parse_screenshot_response() {
+    let text = format!(
+        r#"{{"response":"capture-screen","response-id":"1","status":"200","status-msg":"OK","content-type":"image/png","content-data":"{}"}}"#,
+        base64::encode(png_header())
+    );
+    let response = Response::from_message(ECPMessage::Text { text }).unwrap();
+    let screenshot: Screenshot = Image::from_response(response).unwrap();
+    assert_eq!(screenshot.format, ContentType::Png);
+    assert_eq!((screenshot.width, screenshot.height), (Some(1920), Some(1080)));
+    assert_eq!(screenshot.extension(), "png");
+
+    let text = String::from(r#"{"response":"capture-screen","response-id":"1","status":"200","status-msg":"OK"}"#);
+    let response = Response::from_message(ECPMessage::Text { text }).unwrap();
+    assert!(matches!(Image::from_response(response), Err(RequestError::Content(_))));
+
+    assert!(matches!(RequestError::from_status(403, "Forbidden"), RequestError::Disallowed { .. }));
+}
+
+#[tokio::test]
+async fn capture_screen_disallowed_stand_in() {
+    let (mut connection, server) = ecp_stand_in(|request| {
+        let mut reply: Value = serde_json::from_str(&stand_in_reply(request, None)).unwrap();
+        reply["status"] = json!("403");
+        reply["status-msg"] = json!("Forbidden");
+        vec![reply.to_string()]
+    }).await;
+
+    let error = connection.capture_screen().await.unwrap_err();
+    assert_eq!(error, RequestError::CaptureDisallowed { status_code: 403, status_message: String::from("Forbidden") });
+    assert!(error.to_string().contains("developer settings"));
+    assert!(matches!(connection.request(Get::DeviceInfo).await, Err(RequestError::Disallowed { .. })));
+
+    drop(connection);
+    assert_eq!(server.await.unwrap()[0]["request"], "capture-screen");
+}
+
+#[test]
+fn parse_installed_apps() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<apps>
+    <app id="12" type="appl" version="4.2.81179051">Netflix</app>
+    <app id="tvinput.hdmi1" type="tvin" version="1.0.0">HDMI 1</app>
+</apps>"#;
+    let apps = App::parse_list(xml).unwrap();
+    assert_eq!(apps.len(), 2);
+    assert_eq!(apps[0].id, "12");
+    assert_eq!(apps[0].name, "Netflix");
+    assert_eq!(apps[0].version, "4.2.81179051");
+    assert_eq!(apps[1].app_type, "tvin");
+}
+
+#[test]
+fn icon_cache_round_trip() {
+    let cache = IconCache::new(std::env::temp_dir().join("ecp_test_icon_cache"));
+    let icon = Image::from_bytes(png_header()).unwrap();
+
+    assert_ne!(cache.path(12, "4.2.1"), cache.path(12, "4.2.2"));
+    cache.put(12, "4.2.1", &icon).unwrap();
+    assert_eq!(cache.get(12, "4.2.1"), Some(icon));
+    assert_eq!(cache.get(12, "4.2.2"), None);
+    let _ = std::fs::remove_dir_all(&cache.dir);
+}
+
+#[test]
+fn parse_key_macro() {
+    let parsed = Macro::parse(r#"Home, wait 2s, Down x3, Select, type "news, weather""#).unwrap();
+    let down = Step::Press { key: String::from("Down") };
+    assert_eq!(parsed.steps, vec![
+        Step::Press { key: String::from("Home") },
+        Step::Wait { duration: Duration::from_secs(2) },
+        down.clone(), down.clone(), down.clone(),
+        Step::Press { key: String::from("Select") },
+        Step::Type { text: String::from("news, weather") },
+    ]);
+
+    let json = r#"["Home", {"wait_ms": 2000}, {"press": "Down", "repeat": 3}, "Select", {"type": "news, weather"}]"#;
+    assert_eq!(Macro::from_json(json).unwrap(), parsed);
+
+    let error = Macro::parse("Home, wait soon").unwrap_err();
+    assert_eq!(error.position, 1);
+    assert!(Macro::parse("Down x").is_err());
+
+    assert_eq!(literal_keys("a b"), vec!["Lit_a", "Lit_%20", "Lit_b"]);
+}
+
+#[tokio::test]
+async fn run_macro_unopened() {
+    let mut connection = Connection::new(DEVICE_IP, b"key".to_vec());
+    let steps = Macro::parse("wait 10ms, Home, Select").unwrap();
+
+    let report = connection.run_macro(&steps, &MacroAbort::new()).await;
+    assert_eq!(report.results.len(), 2);
+    assert_eq!(report.results[1].outcome, Err(RequestError::NotConnected));
+    assert!(!report.is_success());
+
+    let abort = MacroAbort::new();
+    abort.abort();
+    let report = connection.run_macro(&steps, &abort).await;
+    assert!(report.aborted);
+    assert!(report.results.is_empty());
+}
+
+#[cfg(feature = "scripting")]
+#[tokio::test(flavor = "multi_thread")]
+async fn run_script_unopened() {
+    use crate::script::ScriptEngine;
+
+    let engine = ScriptEngine::new(Connection::new(DEVICE_IP, b"key".to_vec()));
+    let result = engine.run("let total = 0; for i in 0..3 { total += i; } total").unwrap();
+    assert_eq!(result.as_int(), Ok(3));
+
+    let error = engine.run(r#"press("Home")"#).unwrap_err();
+    assert!(error.message.contains("not open"));
+    assert!(!engine.connection().is_open());
+}
+
+#[cfg(feature = "scripting")]
+#[tokio::test]
+async fn run_script_single_threaded() {
+    use crate::script::ScriptEngine;
+
+    let engine = ScriptEngine::new(Connection::new(DEVICE_IP, b"key".to_vec()));
+    let error = engine.run(r#"press("Home")"#).unwrap_err();
+    assert!(error.message.contains("multi-threaded"));
+    assert!(engine.run("sleep(1)").is_err());
+
+    // Outside any runtime
+    let error = std::thread::spawn(|| {
+        ScriptEngine::new(Connection::new(DEVICE_IP, b"key".to_vec())).run("sleep(1)").unwrap_err()
+    }).join().unwrap();
+    assert!(error.message.contains("inside a tokio runtime"));
+}
+
+#[test]
+fn check_wait_conditions() {
+    let active_app = r#"<active-app><app id="12" type="appl" version="4.2.1">Netflix</app></active-app>"#;
+    assert!(Condition::AppActive { app_id: String::from("12") }.is_met(active_app));
+    assert!(!Condition::AppActive { app_id: String::from("13") }.is_met(active_app));
+
+    let player = r#"<player error="false" state="play"><plugin id="12" name="Netflix"/></player>"#;
+    assert!(Condition::media_playing().is_met(player));
+    assert!(!Condition::MediaState { state: String::from("pause") }.is_met(player));
+
+    assert!(Condition::PowerOn.is_met("<device-info><power-mode>PowerOn</power-mode></device-info>"));
+    assert!(!Condition::PowerOn.is_met("<device-info><power-mode>DisplayOff</power-mode></device-info>"));
+
+    assert!(Condition::TexteditVisible.is_met(r#"{"textedit-state":{"textedit-id":"12","text":""}}"#));
+    assert!(!Condition::TexteditVisible.is_met(r#"{"textedit-state":{"textedit-id":"none"}}"#));
+
+    assert!(Condition::HomeScreen.is_met("<active-app><app>Roku</app></active-app>"));
+    assert!(Condition::HomeScreen.is_met(
+        r#"<active-app><app>Roku</app><screensaver id="55545" type="ssvr" version="2.0.1">Default screensaver</screensaver></active-app>"#
+    ));
+    assert!(!Condition::HomeScreen.is_met(active_app));
+    assert!(!Condition::HomeScreen.is_met("<active-app/>"));
+    assert!(!Condition::HomeScreen.is_met("not xml"));
+}
+
+#[test]
+fn parse_notification() {
+    let text = r#"{"notify":"media-player-state-changed","param-media-player-state":"play","timestamp":"1.2"}"#;
+    let notification = Notification::from_text(text).unwrap();
+    assert_eq!(notification.event, "media-player-state-changed");
+    assert_eq!(notification.param("media-player-state"), Some("play"));
+    assert_eq!(Notification::from_text(r#"{"response":"query-apps"}"#), None);
+
+    let events = Set::RequestEvents { events: vec![String::from("power-mode-changed"), String::from("textedit-opened")] };
+    assert_eq!(events.params().unwrap()["param-events"], "+power-mode-changed,+textedit-opened");
+}
+
+#[tokio::test]
+async fn wait_for_unopened() {
+    let mut connection = Connection::new(DEVICE_IP, b"key".to_vec());
+    let result = connection.wait_for(&Condition::PowerOn, Duration::from_millis(10)).await;
+    assert_eq!(result, Err(RequestError::NotConnected));
+    assert!(!connection.is_subscribed("power-mode-changed"));
+}
+
+#[test]
+fn deep_link_params() {
+    let deep_link = DeepLink::new("tt 42", MediaType::ShortFormVideo).param("startTime", "90&more");
+    let launch = Set::LaunchApp { channel_id: 12, deep_link: Some(deep_link) };
+    let params = launch.params().unwrap();
+    assert_eq!(params["param-channel-id"], "12");
+    assert_eq!(params["param-params"], "contentId=tt%2042&mediaType=shortFormVideo&startTime=90%26more");
+
+    let plain = Set::LaunchApp { channel_id: 12, deep_link: None };
+    assert!(!plain.params().unwrap().contains_key("param-params"));
+
+    let input = Set::Input { params: vec![(String::from("action"), String::from("next"))] };
+    assert_eq!(input.subject(), "input");
+    assert_eq!(input.params().unwrap()["param-params"], "action=next");
+}
+
+#[test]
+fn find_app_by_name() {
+    let app = |id: &str, name: &str| App {
+        id: String::from(id),
+        name: String::from(name),
+        app_type: String::from("appl"),
+        subtype: String::new(),
+        version: String::from("1.0.0"),
+    };
+    let apps = vec![
+        app("12", "Netflix"),
+        app("13", "Prime Video"),
+        app("2285", "Hulu"),
+        app("41468", "Disney+"),
+        app("837", "YouTube"),
+        app("195316", "YouTube Kids"),
+    ];
+
+    assert_eq!(App::find_by_name(&apps, "netflix").unwrap().id, "12");
+    assert_eq!(App::find_by_name(&apps, "disney").unwrap().id, "41468");
+    assert_eq!(App::find_by_name(&apps, "youtube").unwrap().id, "837");
+    assert_eq!(App::find_by_name(&apps, "prime").unwrap().id, "13");
+    assert_eq!(App::find_by_name(&apps, "netflx").unwrap().id, "12");
+
+    assert_eq!(
+        App::find_by_name(&apps, "tube"),
+        Err(RequestError::AmbiguousApp {
+            name: String::from("tube"),
+            candidates: vec![String::from("YouTube"), String::from("YouTube Kids")],
+        })
+    );
+    assert!(matches!(App::find_by_name(&apps, "Crunchyroll"), Err(RequestError::UnknownApp { .. })));
+}
+
+#[test]
+fn install_command() {
+    let install = Set::InstallApp { channel_id: 12 };
+    assert_eq!(install.subject(), "install");
+    assert_eq!(install.params().unwrap()["param-channel-id"], "12");
+}
+
+#[test]
+fn digest_auth() {
+    let challenge = parse_digest_challenge(r#"Digest realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#);
+    assert_eq!(challenge["realm"], "testrealm@host.com");
+    assert_eq!(challenge["qop"], "auth,auth-int");
+    assert_eq!(challenge["opaque"], "5ccc069c403ebaf9f0171e9517f40e41");
+
+    // RFC 2617 section 3.5 example
+    let response = digest_response(
+        "Mufasa", "Circle Of Life", "testrealm@host.com", "dcd98b7102dd2f0e8b11d0f600bfb0c093",
+        "GET", "/dir/index.html", "00000001", "0a4f113b"
+    );
+    assert_eq!(response, "6629fae49393a05397450978507c4ef1");
+}
+
+#[test]
+fn parse_installer_page() {
+    let success = r#"<script>Shell.create('Roku.Message').trigger('Set message type', 'success').trigger('Set message content', 'Install Success.').trigger('Render', node);</script>"#;
+    assert_eq!(InstallerResult::from_page(success), InstallerResult::Success { messages: vec![String::from("Install Success.")] });
+
+    let failure = r#"<script>Shell.create('Roku.Message').trigger('Set message type', 'error').trigger('Set message content', 'Install Failure: Compilation Failed.').trigger('Render', node);</script>"#;
+    assert!(!InstallerResult::from_page(failure).is_success());
+
+    let legacy = r#"<font color="red">Application Received: Identical to previous version -- not replacing.</font>"#;
+    assert!(InstallerResult::from_page(legacy).is_success());
+}
+
+#[tokio::test]
+async fn developer_install_stand_in() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    let server = tokio::spawn(async move {
+        let mut requests = vec![];
+        for reply in [
+            "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Digest realm=\"rokudev\", nonce=\"abc123\", qop=\"auth\"\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<script>x.trigger('Set message type', 'success').trigger('Set message content', 'Install Success.')</script>",
+        ] {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut raw = vec![];
+            let mut buffer = [0; 4096];
+            // Read until the announced body has arrived
+            loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                raw.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&raw).into_owned();
+                if let Some(split) = text.find("\r\n\r\n") {
+                    let length = text.lines()
+                        .find_map(|line| line.strip_prefix("Content-Length: "))
+                        .and_then(|length| length.trim().parse::<usize>().ok())
+                        .unwrap_or(0);
+                    if raw.len() >= split + 4 + length || read == 0 {
+                        break;
+                    }
+                }
+            }
+            stream.write_all(reply.as_bytes()).await.unwrap();
+            requests.push(String::from_utf8_lossy(&raw).into_owned());
+        }
+        requests
+    });
+
+    let client = DeveloperClient::new(&address, b"devpass".to_vec());
+    let result = client.install(b"PK\x03\x04zip").await.unwrap();
+    assert!(result.is_success());
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].starts_with("POST /plugin_install HTTP/1.1"));
+    assert!(requests[1].contains(r#"Authorization: Digest username="rokudev", realm="rokudev", nonce="abc123", uri="/plugin_install", qop=auth"#));
+    assert!(requests[1].contains("name=\"mysubmit\"\r\n\r\nInstall"));
+    assert!(requests[1].contains("PK\x03\x04zip"));
+}
+
+#[tokio::test]
+async fn developer_install_times_out() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    // Accept the connection and hold it open without replying
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        drop(stream);
+    });
+
+    let mut client = DeveloperClient::new(&address, b"devpass".to_vec());
+    client.timeout = Duration::from_millis(200);
+    assert_eq!(client.install(b"PK\x03\x04zip").await, Err(RequestError::TimedOut));
+    server.abort();
+}
+
+#[tokio::test]
+async fn debug_console_stand_in() {
+    use futures_util::StreamExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        stream.write_all(concat!(
+            "------ Running dev 'Test' main ------\r\n",
+            "Loading feed\r\n",
+            "BrightScript Micro Debugger.\r\n",
+            "'Dot' Operator attempted with invalid BrightScript Component or interface reference. (runtime error &hec) in pkg:/source/main.brs(12)\r\n",
+            "Backtrace:\r\n",
+            "#0  Function main() As Void\r\n",
+            "   file/line: pkg:/source/main.brs(12)\r\n",
+            "Local Variables:\r\n",
+            "global           Interface:ifGlobal\r\n",
+            "Brightscript Debugger> ",
+        ).as_bytes()).await.unwrap();
+
+        let mut command = [0; 16];
+        let read = stream.read(&mut command).await.unwrap();
+        String::from_utf8_lossy(&command[..read]).into_owned()
+    });
+
+    let mut console = DebugConsole::connect(&address).await.unwrap();
+    let events: Vec<ConsoleEvent> = console.events().take(11).collect().await;
+    console.send_command("bt").await.unwrap();
+    assert_eq!(server.await.unwrap(), "bt\r\n");
+
+    let lines: Vec<&str> = events.iter()
+        .filter_map(|event| match event { ConsoleEvent::Line(line) => Some(line.text.as_str()), _ => None })
+        .collect();
+    assert_eq!(lines.len(), 10);
+    assert_eq!(lines[1], "Loading feed");
+    assert_eq!(lines[9], "Brightscript Debugger> ");
+
+    match events.last() {
+        Some(ConsoleEvent::Crash(crash)) => {
+            assert!(crash.error.as_deref().unwrap().contains("runtime error &hec"));
+            assert_eq!(crash.backtrace, vec!["#0  Function main() As Void", "file/line: pkg:/source/main.brs(12)"]);
+            assert_eq!(crash.lines.len(), 8);
         }
+        other => panic!("expected crash report, got {:?}", other),
     }
 }
 
+#[test]
+fn parse_ui_tree() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<app-ui>
+    <topscreen>
+        <plugin id="dev" name="Test"/>
+        <screen focused="true" type="RoSGScreen">
+            <MainScene name="main" focused="true" bounds="{0, 0, 1920, 1080}">
+                <Button name="play" text="Play" focused="true" bounds="{100, 900, 200, 80}"/>
+                <Button name="search" text="Search" bounds="{400, 900, 200, 80}"/>
+                <Label text="Now showing: News" bounds="{100, 100, 800, 40}"/>
+            </MainScene>
+        </screen>
+    </topscreen>
+</app-ui>"#;
+    let tree = UiNode::parse(xml).unwrap();
+    assert_eq!(tree.tag, "app-ui");
+
+    let focused = tree.focused().unwrap();
+    assert_eq!(focused.attribute("name"), Some("play"));
+
+    let search = tree.find(&Selector::Text(String::from("Search"))).unwrap();
+    assert!(!search.is_focused());
+    assert_eq!(search.bounds(), Some(Bounds { x: 400.0, y: 900.0, width: 200.0, height: 80.0 }));
+    assert_eq!(direction_toward(focused, search), Some("Right"));
+
+    let label = tree.find(&Selector::TextContains(String::from("News"))).unwrap();
+    assert_eq!(direction_toward(focused, label), Some("Up"));
+
+    assert_eq!(tree.find_all(&Selector::Tag(String::from("Button"))).len(), 2);
+    let both = Selector::All(vec![Selector::Tag(String::from("Button")), Selector::Focused]);
+    assert_eq!(tree.find(&both).unwrap().attribute("name"), Some("play"));
+    assert!(tree.find(&Selector::name("settings")).is_none());
+}
+
+#[test]
+fn parse_channel_performance() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<chanperf>
+    <plugin id="dev">
+        <cpu-percent>
+            <durationms>1000</durationms>
+            <user>12.5</user>
+            <sys>3.0</sys>
+        </cpu-percent>
+        <memory>
+            <used>52428800</used>
+            <res>41943040</res>
+            <anon>31457280</anon>
+            <file>10485760</file>
+            <shared>1048576</shared>
+            <swap>0</swap>
+        </memory>
+    </plugin>
+    <status>OK</status>
+</chanperf>"#;
+    let perf = ChannelPerf::parse(xml).unwrap();
+    assert_eq!(perf.plugin_id, "dev");
+    assert_eq!(perf.cpu_user, 12.5);
+    assert_eq!(perf.memory_used, 52428800);
+
+    let failed = "<chanperf><status>FAILED</status><error>Channel not running</error></chanperf>";
+    assert_eq!(ChannelPerf::parse(failed), Err(RequestError::Content(String::from("Channel not running"))));
+
+    let sample = PerfSample { timestamp: std::time::UNIX_EPOCH + Duration::from_millis(1500), perf };
+
+    let mut csv = PerfLog::new(vec![], SampleFormat::Csv);
+    csv.write(&sample).unwrap();
+    csv.write(&sample).unwrap();
+    let csv = String::from_utf8(csv.writer).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("timestamp_ms,plugin_id,"));
+    assert_eq!(lines[1], "1500,dev,1000,12.5,3,52428800,41943040,31457280,10485760,1048576,0");
+
+    let mut jsonl = PerfLog::new(vec![], SampleFormat::JsonLines);
+    jsonl.write(&sample).unwrap();
+    let line: serde_json::Value = serde_json::from_slice(&jsonl.writer).unwrap();
+    assert_eq!(line["timestamp_ms"], 1500);
+    assert_eq!(line["memory_used"], 52428800);
+}
+
+#[test]
+fn diff_node_stats() {
+    let before = NodeStats::parse(r#"<sgnodes><All_Nodes>
+        <MainScene name="main">
+            <Group><Label/><Poster/></Group>
+        </MainScene>
+    </All_Nodes></sgnodes>"#).unwrap();
+    assert_eq!(before.total, 4);
+    assert_eq!(before.roots, 1);
+    assert_eq!(before.orphans, 0);
+    assert_eq!(before.by_type["Label"], 1);
+
+    let after = NodeStats::parse(r#"<sgnodes><All_Nodes>
+        <MainScene name="main">
+            <Group><Label/><Label/><Label/></Group>
+        </MainScene>
+        <ContentNode><ContentNode/></ContentNode>
+    </All_Nodes></sgnodes>"#).unwrap();
+    assert_eq!(after.roots, 2);
+    assert_eq!(after.orphans, 1);
+
+    let diff = NodeStats::diff(&before, &after);
+    assert_eq!(diff.total_delta, 3);
+    assert_eq!(diff.orphan_delta, 1);
+    assert_eq!(diff.grown.iter().map(|change| change.node_type.as_str()).collect::<Vec<&str>>(), vec!["ContentNode", "Label"]);
+    assert_eq!(diff.grown[0].delta(), 2);
+    assert_eq!(diff.shrunk[0].node_type, "Poster");
+}
+
+#[test]
+fn parse_media_player() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<player error="false" state="play">
+    <plugin bandwidth="25000000 bps" id="12" name="Netflix"/>
+    <format audio="aac" captions="none" container="mp4" drm="widevine" video="hevc"/>
+    <buffering current="1000" max="1000" target="0"/>
+    <new_stream speed="128000 bps"/>
+    <position>125000 ms</position>
+    <duration>3600000 ms</duration>
+    <is_live>false</is_live>
+</player>"#;
+    let player = MediaPlayer::parse(xml).unwrap();
+    assert_eq!(player.state, PlayerState::Play);
+    assert_eq!(player.plugin_id.as_deref(), Some("12"));
+    assert_eq!(player.position, Some(Duration::from_millis(125000)));
+    assert_eq!(player.duration, Some(Duration::from_secs(3600)));
+    assert_eq!(player.buffering, Some(Buffering { current: 1000, max: 1000, target: 0 }));
+    assert!(!player.is_live);
+
+    assert!(player.state.is_playing());
+
+    let idle = MediaPlayer::parse(r#"<player error="false" state="close"/>"#).unwrap();
+    assert!(idle.state.is_stopped());
+    assert!(!idle.state.is_playing());
+    assert_eq!(idle.position, None);
+    assert_eq!(PlayerState::parse("rewinding"), PlayerState::Unknown(String::from("rewinding")));
+}
+
+#[tokio::test]
+async fn pause_and_seek_when_idle_stand_in() {
+    let (mut connection, server) = ecp_stand_in(|request| {
+        vec![stand_in_reply(request, Some(r#"<player error="false" state="stop"><position>5000 ms</position></player>"#))]
+    }).await;
+
+    let nothing_playing = Err(RequestError::Content(String::from("nothing playing")));
+    assert_eq!(connection.pause(Duration::from_secs(1)).await, nothing_playing);
+    assert_eq!(connection.seek_relative(10000, Duration::from_secs(1)).await, nothing_playing);
+
+    drop(connection);
+    assert!(server.await.unwrap().iter().all(|request| request["request"] == "query-media-player"));
+}
+
+#[test]
+fn track_playback_events() {
+    let player = |state: &str, position_ms: Option<u64>| MediaPlayer {
+        state: PlayerState::parse(state),
+        error: false,
+        plugin_id: Some(String::from("12")),
+        plugin_name: None,
+        position: position_ms.map(Duration::from_millis),
+        duration: None,
+        is_live: false,
+        buffering: None,
+    };
+    let start = std::time::Instant::now();
+    let at = |seconds: u64| start + Duration::from_secs(seconds);
+
+    let mut tracker = PlaybackTracker::new();
+    let mut events = vec![];
+    events.extend(tracker.update(at(0), &player("open", None)));
+    events.extend(tracker.update(at(1), &player("buffer", Some(0))));
+    events.extend(tracker.update(at(3), &player("play", Some(0))));
+    events.extend(tracker.update(at(13), &player("buffer", Some(10000))));
+    events.extend(tracker.update(at(15), &player("play", Some(10000))));
+    events.extend(tracker.update(at(20), &player("pause", Some(15000))));
+    events.extend(tracker.update(at(25), &player("play", Some(15000))));
+    events.extend(tracker.update(at(30), &player("play", Some(80000))));
+    events.extend(tracker.update(at(35), &player("finished", None)));
+
+    assert_eq!(events, vec![
+        PlaybackEvent::Started { time_to_first_frame: Duration::from_secs(3) },
+        PlaybackEvent::BufferingStarted { position: Some(Duration::from_secs(10)) },
+        PlaybackEvent::BufferingEnded { stalled: Duration::from_secs(2) },
+        PlaybackEvent::Paused { position: Some(Duration::from_secs(15)) },
+        PlaybackEvent::Resumed { position: Some(Duration::from_secs(15)) },
+        PlaybackEvent::Seeked { from: Duration::from_secs(15), to: Duration::from_secs(80) },
+        PlaybackEvent::Ended { position: Some(Duration::from_secs(80)) },
+    ]);
+
+    let stats = tracker.stats();
+    assert_eq!(stats.play_time, Duration::from_secs(25));
+    assert_eq!(stats.rebuffer_time, Duration::from_secs(2));
+    assert_eq!(stats.rebuffer_count, 1);
+    assert_eq!(stats.seek_count, 1);
+    assert!((stats.rebuffer_ratio() - 2.0 / 27.0).abs() < 1e-9);
+}
+
+#[test]
+fn volume_presses() {
+    let volume = Volume::with_level(10);
+    assert_eq!(volume.presses_to(25), Some(("VolumeUp", 15)));
+    assert_eq!(volume.presses_to(4), Some(("VolumeDown", 6)));
+    assert_eq!(volume.presses_to(500), Some(("VolumeUp", 90)));
+    assert_eq!(Volume::new().presses_to(10), None);
+}
+
+#[tokio::test]
+async fn set_volume_stand_in() {
+    let key_presses = |requests: Vec<Value>| -> Vec<String> {
+        requests.iter()
+            .filter(|request| request["request"] == "key-press")
+            .map(|request| String::from(request["param-key"].as_str().unwrap_or_default()))
+            .collect()
+    };
+
+    // Level reported by the audio settings
+    let (mut connection, server) = ecp_stand_in(|request| {
+        vec![stand_in_reply(request, Some(r#"<audio-settings><setting id="volume" value="10" min="0" max="100"/></audio-settings>"#))]
+    }).await;
+    let mut volume = Volume::new();
+    volume.press_delay = Duration::ZERO;
+    volume.set_volume(&mut connection, 13).await.unwrap();
+    assert_eq!(volume.level(), Some(13));
+    volume.set_muted(&mut connection, true).await.unwrap();
+    volume.set_muted(&mut connection, true).await.unwrap();
+    assert!(volume.is_muted());
+    drop(connection);
+    assert_eq!(key_presses(server.await.unwrap()), ["VolumeUp", "VolumeUp", "VolumeUp", "VolumeMute"]);
+
+    // No reported level, so calibrate down to zero first
+    let (mut connection, server) = ecp_stand_in(|request| {
+        vec![stand_in_reply(request, Some(r#"<audio-settings><setting id="bass" value="0"/></audio-settings>"#))]
+    }).await;
+    let mut volume = Volume::new();
+    volume.max = 3;
+    volume.press_delay = Duration::ZERO;
+    volume.set_volume(&mut connection, 2).await.unwrap();
+    assert_eq!(volume.level(), Some(2));
+    drop(connection);
+    assert_eq!(
+        key_presses(server.await.unwrap()),
+        ["VolumeDown", "VolumeDown", "VolumeDown", "VolumeUp", "VolumeUp"]
+    );
+
+    // No audio settings query, so only the first call calibrates
+    let (mut connection, server) = ecp_stand_in(|request| {
+        let mut reply: Value = serde_json::from_str(&stand_in_reply(request, None)).unwrap();
+        if request["request"] == "query-audio-settings" {
+            reply["status"] = json!("404");
+            reply["status-msg"] = json!("Not Found");
+        }
+        vec![reply.to_string()]
+    }).await;
+    let mut volume = Volume::new();
+    volume.max = 3;
+    volume.press_delay = Duration::ZERO;
+    volume.set_volume(&mut connection, 1).await.unwrap();
+    volume.set_volume(&mut connection, 2).await.unwrap();
+    assert_eq!(volume.level(), Some(2));
+
+    // Already at the target, so staying muted
+    volume.toggle_mute(&mut connection).await.unwrap();
+    volume.set_volume(&mut connection, 2).await.unwrap();
+    assert!(volume.is_muted());
+    drop(connection);
+    assert_eq!(
+        key_presses(server.await.unwrap()),
+        ["VolumeDown", "VolumeDown", "VolumeDown", "VolumeUp", "VolumeUp", "VolumeMute"]
+    );
+}
+
+#[test]
+fn parse_tv_input() {
+    assert_eq!(TvInput::parse("tvinput.hdmi2"), TvInput::Hdmi2);
+    assert_eq!(TvInput::parse("HDMI 3"), TvInput::Hdmi3);
+    assert_eq!(TvInput::parse("tvinput.dtv"), TvInput::Tuner);
+    assert_eq!(TvInput::parse("tvinput.cvbs"), TvInput::Av1);
+    assert_eq!(TvInput::parse("Game Console"), TvInput::Named(String::from("Game Console")));
+    assert_eq!(TvInput::Av1.key(), Some("InputAV1"));
+    assert_eq!(TvInput::Named(String::from("Game Console")).key(), None);
+
+    let xml = r#"<tv-active-input><input id="tvinput.hdmi1" name="Cable Box"/></tv-active-input>"#;
+    assert_eq!(TvInput::parse_active(xml), Ok(TvInput::Hdmi1));
+    assert_eq!(TvInput::parse_active("<active-input>tvinput.dtv</active-input>"), Ok(TvInput::Tuner));
+    assert!(TvInput::parse_active("<tv-active-input/>").is_err());
+    assert_eq!(TvInput::parse("tvinput.hdmi1").id(), TvInput::Hdmi1.id());
+}
+
+#[tokio::test]
+async fn switch_input_stand_in() {
+    let mut active = String::from("tvinput.dtv");
+    let (mut connection, server) = ecp_stand_in(move |request| {
+        match request["request"].as_str() {
+            Some("key-press") if request["param-key"] == "InputHDMI2" => active = String::from("tvinput.hdmi2"),
+            Some("launch") => active = String::from(request["param-channel-id"].as_str().unwrap_or_default()),
+            _ => {}
+        }
+        let xml = format!(r#"<tv-active-input><input id="{}"/></tv-active-input>"#, active);
+        vec![stand_in_reply(request, Some(&xml))]
+    }).await;
+
+    connection.switch_input(TvInput::Hdmi2, Duration::from_secs(5)).await.unwrap();
+    assert_eq!(connection.active_tv_input().await.unwrap(), TvInput::Hdmi2);
+    let console = TvInput::Named(String::from("tvinput.game-console"));
+    connection.switch_input(console.clone(), Duration::from_secs(5)).await.unwrap();
+    assert_eq!(connection.active_tv_input().await.unwrap(), console);
+    // Already active, so nothing is sent
+    connection.switch_input(console, Duration::from_secs(5)).await.unwrap();
+
+    drop(connection);
+    let subjects: Vec<String> = server.await.unwrap()
+        .iter()
+        .map(|request| String::from(request["request"].as_str().unwrap_or_default()))
+        .collect();
+    assert_eq!(subjects, [
+        "query-tv-active-input", "key-press", "query-tv-active-input", "query-tv-active-input",
+        "query-tv-active-input", "launch", "query-tv-active-input", "query-tv-active-input",
+        "query-tv-active-input",
+    ]);
+}
+
+#[test]
+fn parse_tv_channels() {
+    let xml = r#"<tv-channels>
+        <channel>
+            <number>2.1</number>
+            <name>KTVU-HD</name>
+            <type>air-digital</type>
+            <physical-channel>44</physical-channel>
+            <user-hidden>false</user-hidden>
+            <user-favorite>true</user-favorite>
+        </channel>
+        <channel>
+            <number>5.1</number>
+            <name>KPIX</name>
+            <type>air-digital</type>
+            <user-hidden>true</user-hidden>
+        </channel>
+    </tv-channels>"#;
+    let channels = TvChannel::parse_list(xml).unwrap();
+    assert_eq!(channels, vec![
+        TvChannel {
+            number: String::from("2.1"),
+            name: String::from("KTVU-HD"),
+            channel_type: String::from("air-digital"),
+            physical_id: Some(String::from("44")),
+            hidden: false,
+            favorite: true,
+        },
+        TvChannel {
+            number: String::from("5.1"),
+            name: String::from("KPIX"),
+            channel_type: String::from("air-digital"),
+            physical_id: None,
+            hidden: true,
+            favorite: false,
+        },
+    ]);
+
+    let active = r#"<tv-channel><channel><number>2.1</number><name>KTVU-HD</name><type>air-digital</type></channel></tv-channel>"#;
+    assert_eq!(TvChannel::parse_active(active).unwrap().number, "2.1");
+    assert!(TvChannel::parse_active("<tv-channel/>").is_err());
+
+    let launch = Set::LaunchTvInput {
+        input_id: TvInput::Tuner.id(),
+        params: vec![(String::from("ch"), String::from("2.1"))],
+    };
+    assert_eq!(launch.subject(), "launch");
+    let params = launch.params().unwrap();
+    assert_eq!(params["param-channel-id"], "tvinput.dtv");
+    assert_eq!(params["param-params"], "ch=2.1");
+}
+
+#[tokio::test]
+async fn tune_channel_stand_in() {
+    let mut tuned = String::from("2.1");
+    let mut polls = 0;
+    let (mut connection, server) = ecp_stand_in(move |request| {
+        match request["request"].as_str() {
+            Some("launch") => {
+                tuned = String::from(request["param-params"].as_str().unwrap_or_default().trim_start_matches("ch="));
+                polls = 0;
+                vec![stand_in_reply(request, None)]
+            }
+            _ => {
+                // The tuner reports no channel while it starts, then the old one, then the new one
+                polls += 1;
+                let xml = match polls {
+                    1 => String::from("<tv-channel/>"),
+                    2 => String::from("<tv-channel><channel><number>2.1</number></channel></tv-channel>"),
+                    _ => format!("<tv-channel><channel><number>{}</number><name>KQED</name></channel></tv-channel>", tuned),
+                };
+                vec![stand_in_reply(request, Some(&xml))]
+            }
+        }
+    }).await;
+
+    let channel = connection.tune_channel("9.1", Duration::from_secs(5)).await.unwrap();
+    assert_eq!((channel.number.as_str(), channel.name.as_str()), ("9.1", "KQED"));
+
+    drop(connection);
+    let requests = server.await.unwrap();
+    assert_eq!(requests[0]["param-channel-id"], "tvinput.dtv");
+    assert_eq!(requests.len(), 4);
+}
+
+#[tokio::test]
+async fn collect_guide_stand_in() {
+    let mut tuned = String::from("2.1");
+    let (mut connection, server) = ecp_stand_in(move |request| {
+        if request["request"].as_str() == Some("launch") {
+            // Channel 5.1 has no signal, so the tuner stays where it was
+            let number = request["param-params"].as_str().unwrap_or_default().trim_start_matches("ch=");
+            if number != "5.1" {
+                tuned = String::from(number);
+            }
+            return vec![stand_in_reply(request, None)];
+        }
+        let mut reply: Value = serde_json::from_str(&stand_in_reply(request, Some(&format!(
+            "<tv-channel><channel><number>{0}</number><program-title>Show on {0}</program-title></channel></tv-channel>",
+            tuned
+        )))).unwrap();
+        // Channel 7.1 tunes but its queries fail
+        if tuned == "7.1" {
+            reply["status"] = json!("500");
+            reply["status-msg"] = json!("Internal Server Error");
+        }
+        vec![reply.to_string()]
+    }).await;
+
+    let channel = |number: &str| TvChannel { number: String::from(number), ..TvChannel::default() };
+    let channels = [channel("5.1"), channel("7.1"), channel("9.1")];
+    let guide = connection.collect_guide(&channels, Duration::from_millis(300)).await.unwrap();
+    assert_eq!(guide.channels, channels);
+    assert_eq!(guide.skipped, vec![channel("5.1"), channel("7.1")]);
+    assert_eq!(guide.programs.len(), 1);
+    assert_eq!((guide.programs[0].channel_number.as_str(), guide.programs[0].title.as_str()), ("9.1", "Show on 9.1"));
+    assert_eq!(connection.active_tv_channel().await.unwrap().number, "2.1");
+
+    drop(connection);
+    let requests = server.await.unwrap();
+    let launches: Vec<Value> = requests.iter()
+        .filter(|request| request["request"] == "launch")
+        .map(|request| request["param-params"].clone())
+        .collect();
+    assert_eq!(launches, vec![json!("ch=5.1"), json!("ch=7.1"), json!("ch=9.1"), json!("ch=2.1")]);
+
+    // The program comes from the reply confirming the tune, without a second query
+    let tuned = requests.iter().position(|request| request["param-params"] == "ch=9.1").unwrap();
+    assert_eq!(requests[tuned + 2]["param-params"], "ch=2.1");
+}
+
+#[test]
+fn lineup_export_and_compare() {
+    let channel = |number: &str, name: &str| TvChannel {
+        number: String::from(number),
+        name: String::from(name),
+        channel_type: String::from("air-digital"),
+        ..TvChannel::default()
+    };
+    let mut saved = Lineup::new(vec![channel("2.1", "KTVU"), channel("5.1", "KPIX"), channel("9.1", "KQED")]);
+    saved.channels[0].physical_id = Some(String::from("44"));
+    saved.channels[1].name = String::from("KPIX \"CBS\", SF");
+    saved.channels[1].favorite = true;
+
+    let csv = saved.to_csv();
+    assert!(csv.starts_with("number,name,type,physical_id,hidden,favorite\n2.1,KTVU,air-digital,44,false,false\n"));
+    assert!(csv.contains("5.1,\"KPIX \"\"CBS\"\", SF\",air-digital,,false,true\n"));
+    assert_eq!(Lineup::parse(&csv).unwrap(), saved);
+
+    let mut connection = Connection::new(DEVICE_IP, b"key".to_vec());
+    connection.port = 8061;
+    saved.channels[2].number = String::from("9 1");
+    let m3u = saved.to_m3u(&connection);
+    saved.channels[2].number = String::from("9.1");
+    assert!(m3u.starts_with("#EXTM3U\n#EXTINF:-1 tvg-chno=\"2.1\" tvg-name=\"KTVU\" group-title=\"air-digital\",KTVU\n"));
+    assert!(m3u.contains("http://192.168.1.226:8061/launch/tvinput.dtv?ch=2.1\n"));
+    assert!(m3u.contains("http://192.168.1.226:8061/launch/tvinput.dtv?ch=9%201\n"));
+    let imported = Lineup::parse(&m3u).unwrap();
+    assert_eq!(imported.channels.len(), 3);
+    assert_eq!(imported.channels[2], channel("9 1", "KQED"));
+
+    let current = Lineup::new(vec![channel("2.1", "KTVU"), channel("9.2", "KQED"), channel("20.1", "KOFY")]);
+    let diff = Lineup::new(vec![channel("2.1", "KTVU"), channel("5.1", "KPIX"), channel("9.1", "KQED")]).compare(&current);
+    assert_eq!(diff.added, vec![channel("20.1", "KOFY")]);
+    assert_eq!(diff.removed, vec![channel("5.1", "KPIX")]);
+    assert_eq!(diff.renumbered, vec![Renumbered {
+        name: String::from("KQED"),
+        before: String::from("9.1"),
+        after: String::from("9.2"),
+    }]);
+    assert!(current.compare(&current).is_empty());
+    assert!(Lineup::parse("channel,name\n1,A\n").is_err());
+}
+
+#[test]
+fn export_xmltv_guide() {
+    let xml = r#"<tv-channel>
+        <channel>
+            <number>2.1</number>
+            <name>KTVU</name>
+            <program-title>News &amp; Weather</program-title>
+            <program-description>Local "headlines"</program-description>
+            <program-start-time>1700000000</program-start-time>
+            <program-duration>1800</program-duration>
+            <program-ratings>TV-G</program-ratings>
+        </channel>
+    </tv-channel>"#;
+    let program = Program::parse_active(xml).unwrap().unwrap();
+    assert_eq!(program.channel_number, "2.1");
+    assert_eq!(program.title, "News & Weather");
+    assert_eq!(program.duration, Some(Duration::from_secs(1800)));
+    assert_eq!(Program::parse_active("<tv-channel><channel><number>5.1</number></channel></tv-channel>"), Ok(None));
+
+    let untimed = Program { channel_number: String::from("5.1"), title: String::from("Unknown start"), ..Program::default() };
+    let guide = ProgramGuide {
+        channels: vec![TvChannel { number: String::from("2.1"), name: String::from("KTVU"), ..TvChannel::default() }],
+        programs: vec![program, untimed],
+        skipped: vec![],
+    };
+    let xmltv = guide.to_xmltv();
+    assert!(xmltv.contains("<channel id=\"2.1\">\n    <display-name>KTVU</display-name>\n"));
+    assert!(xmltv.contains("<programme start=\"20231114221320 +0000\" stop=\"20231114224320 +0000\" channel=\"2.1\">"));
+    assert!(xmltv.contains("<title>News &amp; Weather</title>"));
+    assert!(xmltv.contains("<desc>Local &quot;headlines&quot;</desc>"));
+    assert!(xmltv.contains("<rating>\n      <value>TV-G</value>\n    </rating>"));
+    assert!(!xmltv.contains("Unknown start"));
+    assert!(roxmltree::Document::parse(&xmltv.replace("<!DOCTYPE tv SYSTEM \"xmltv.dtd\">\n", "")).is_ok());
+}
+
+#[test]
+fn parse_audio_settings() {
+    let xml = r#"<audio-settings>
+        <setting id="volume" value="20" min="0" max="100"/>
+        <setting id="bass" value="0" min="-10" max="10" step="2"/>
+        <setting id="audio-mode" value="normal" values="normal, movie, music"/>
+        <setting>
+            <id>speech-clarity</id>
+            <value>off</value>
+            <option>off</option>
+            <option>on</option>
+        </setting>
+        <setting id="night-mode" value="auto"/>
+    </audio-settings>"#;
+    let settings = DeviceSetting::parse_list(xml).unwrap();
+    assert_eq!(settings.len(), 5);
+    assert_eq!(settings[1], DeviceSetting {
+        id: String::from("bass"),
+        value: String::from("0"),
+        allowed: AllowedValues::Range { min: -10, max: 10, step: 2 },
+    });
+    assert_eq!(settings[2].allowed, AllowedValues::List(vec![
+        String::from("normal"), String::from("movie"), String::from("music"),
+    ]));
+    assert_eq!(settings[3].id, "speech-clarity");
+    assert_eq!(settings[3].allowed, AllowedValues::List(vec![String::from("off"), String::from("on")]));
+    assert_eq!(settings[4].allowed, AllowedValues::Any);
+
+    assert!(settings[0].check("100").is_ok());
+    assert!(settings[0].check("101").is_err());
+    assert!(settings[1].check("-4").is_ok());
+    assert!(settings[1].check("3").is_err());
+    assert!(settings[2].check("movie").is_ok());
+    assert_eq!(
+        settings[2].check("loud"),
+        Err(RequestError::InvalidSetting { id: String::from("audio-mode"), value: String::from("loud") })
+    );
+    assert!(settings[4].check("anything").is_ok());
+
+    let query = Get::AudioSetting { id: String::from("bass") };
+    assert_eq!(query.params().unwrap()["param-id"], "bass");
+    let reset = Set::ResetAudioSettings { scope: ResetScope::CurrentInput };
+    assert_eq!(reset.params().unwrap()["param-scope"], "current-input");
+}
+
+#[tokio::test]
+async fn set_audio_setting_stand_in() {
+    let (mut connection, server) = ecp_stand_in(|request| {
+        vec![stand_in_reply(request, Some(r#"<audio-setting><setting id="bass" value="0" min="-10" max="10" step="2"/></audio-setting>"#))]
+    }).await;
+
+    connection.set_audio_setting("bass", "4").await.unwrap();
+    assert_eq!(
+        connection.set_audio_setting("bass", "3").await,
+        Err(RequestError::InvalidSetting { id: String::from("bass"), value: String::from("3") })
+    );
+
+    drop(connection);
+    let requests = server.await.unwrap();
+    let subjects: Vec<&str> = requests.iter().map(|request| request["request"].as_str().unwrap_or_default()).collect();
+    assert_eq!(subjects, ["query-audio-setting", "set-audio-setting", "query-audio-setting"]);
+    assert_eq!((requests[1]["param-id"].as_str(), requests[1]["param-value"].as_str()), (Some("bass"), Some("4")));
+}
+
+#[test]
+fn picture_settings() {
+    let xml = r#"<pq-settings>
+        <setting id="picture-mode" value="standard" values="vivid,standard,movie,sports"/>
+        <setting id="brightness" value="50" min="0" max="100"/>
+        <setting id="hdr-mode" value="auto" values="auto,off"/>
+    </pq-settings>"#;
+    let settings = DeviceSetting::parse_list(xml).unwrap();
+    let mode = settings.iter().find(|setting| setting.id == PictureSetting::PictureMode.id()).unwrap();
+    assert_eq!(mode.value, "standard");
+    assert!(mode.check("movie").is_ok());
+    assert!(mode.check("dynamic").is_err());
+    let brightness = settings.iter().find(|setting| setting.id == PictureSetting::Brightness.id()).unwrap();
+    assert!(brightness.check("75").is_ok());
+    assert!(brightness.check("bright").is_err());
+
+    assert_eq!(Get::ColorSpaceSettings.subject(), "query-pq-color-space-settings");
+    let query = Get::PictureSetting { id: String::from(PictureSetting::ColorTemperature.id()) };
+    assert_eq!(query.params().unwrap()["param-id"], "color-temperature");
+    let command = Set::PictureSetting { id: String::from(PictureSetting::Contrast.id()), value: String::from("60") };
+    let params = command.params().unwrap();
+    assert_eq!((params["param-id"].as_str(), params["param-value"].as_str()), ("contrast", "60"));
+    assert_eq!(Set::ResetPictureSettings { scope: ResetScope::All }.params().unwrap()["param-scope"], "all");
+}
+
+#[tokio::test]
+async fn set_picture_setting_stand_in() {
+    let (mut connection, server) = ecp_stand_in(|request| {
+        vec![stand_in_reply(request, Some(r#"<pq-setting><setting id="hdr-mode" value="auto" values="auto,off"/></pq-setting>"#))]
+    }).await;
+
+    connection.set_picture_setting(PictureSetting::HdrMode, "off").await.unwrap();
+    assert_eq!(
+        connection.set_picture_setting(PictureSetting::HdrMode, "on").await,
+        Err(RequestError::InvalidSetting { id: String::from("hdr-mode"), value: String::from("on") })
+    );
+
+    drop(connection);
+    let requests = server.await.unwrap();
+    let subjects: Vec<&str> = requests.iter().map(|request| request["request"].as_str().unwrap_or_default()).collect();
+    assert_eq!(subjects, ["query-pq-setting", "set-pq-setting", "query-pq-setting"]);
+    assert_eq!(requests[0]["param-id"], "hdr-mode");
+    assert_eq!((requests[1]["param-id"].as_str(), requests[1]["param-value"].as_str()), (Some("hdr-mode"), Some("off")));
+}
+
+#[tokio::test]
+async fn drop_late_replies() {
+    let mut held = None;
+    let (mut connection, server) = ecp_stand_in(move |request| {
+        match request["request"].as_str() {
+            // Hold the first reply back until the next request
+            Some("query-device-info") => {
+                held = Some(stand_in_reply(request, Some("<device-info/>")));
+                vec![]
+            }
+            _ => {
+                let mut frames: Vec<String> = held.take().into_iter().collect();
+                // Control and binary frames ahead of the reply are passed over
+                frames.push(String::new());
+                frames.push(stand_in_reply(request, Some(r#"<player state="play"/>"#)));
+                frames
+            }
+        }
+    }).await;
+    connection.request_timeout = Some(Duration::from_millis(200));
+
+    assert_eq!(connection.request(Get::DeviceInfo).await, Err(RequestError::NoResponse));
+    let response = connection.request(Get::MediaPlayer).await.unwrap();
+    assert_eq!(response.text(), Some(r#"<player state="play"/>"#));
+
+    drop(connection);
+    assert_eq!(server.await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn app_icon_cached_per_session() {
+    let (mut connection, server) = ecp_stand_in(|request| {
+        match request["request"].as_str() {
+            Some("query-apps") => vec![stand_in_reply(request, Some(
+                r#"<apps><app id="12" type="appl" version="4.2.1">Netflix</app></apps>"#
+            ))],
+            _ => {
+                let mut reply: Value = serde_json::from_str(&stand_in_reply(request, None)).unwrap();
+                reply["content-type"] = json!("image/png");
+                reply["content-data"] = json!(base64::encode(png_header()));
+                vec![reply.to_string()]
+            }
+        }
+    }).await;
+    let cache = IconCache::new(std::env::temp_dir().join("ecp_test_icon_session"));
+    let _ = std::fs::remove_dir_all(&cache.dir);
+    connection.icon_cache = Some(cache.clone());
+
+    for _ in 0..3 {
+        assert_eq!(connection.app_icon(12).await.unwrap().width, Some(1920));
+    }
+    assert!(cache.get(12, "4.2.1").is_some());
+    assert!(connection.app_icon(99).await.is_ok());
+    assert!(cache.get(99, "").is_none());
+
+    drop(connection);
+    let subjects: Vec<String> = server.await.unwrap()
+        .iter()
+        .map(|request| String::from(request["request"].as_str().unwrap_or_default()))
+        .collect();
+    assert_eq!(subjects, ["query-apps", "query-icon", "query-apps", "query-icon"]);
+    let _ = std::fs::remove_dir_all(&cache.dir);
+}
+
+#[tokio::test]
+async fn abort_macro_mid_step() {
+    let mut connection = Connection::new(DEVICE_IP, b"key".to_vec());
+    let abort = MacroAbort::new();
+    let trigger = abort.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        trigger.abort();
+    });
+    let report = connection.run_macro(&Macro::parse("wait 5s").unwrap(), &abort).await;
+    assert!(report.aborted);
+    assert!(!report.is_success());
+
+    let abort = MacroAbort::new();
+    let trigger = abort.clone();
+    let mut presses = 0;
+    let (mut connection, server) = ecp_stand_in(move |request| {
+        presses += 1;
+        if presses == 2 {
+            trigger.abort();
+        }
+        vec![stand_in_reply(request, None)]
+    }).await;
+    let report = connection.run_macro(&Macro::parse(r#"type "abcd", Home"#).unwrap(), &abort).await;
+    assert!(report.aborted);
+    assert_eq!(report.results.len(), 1);
+    assert!(!report.is_success());
+
+    drop(connection);
+    assert_eq!(server.await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn wait_for_keeps_notifications() {
+    let mut polls = 0;
+    let (mut connection, server) = ecp_stand_in(move |request| {
+        if request["request"] != "query-media-player" {
+            return vec![stand_in_reply(request, None)];
+        }
+        polls += 1;
+        if polls == 1 {
+            vec![
+                stand_in_reply(request, Some(r#"<player state="pause"/>"#)),
+                String::from(r#"{"notify":"plugin-ui-run","param-plugin-id":"12"}"#),
+                String::from(r#"{"notify":"media-player-state-changed","param-media-player-state":"play"}"#),
+            ]
+        }
+        else {
+            vec![stand_in_reply(request, Some(r#"<player state="play"/>"#))]
+        }
+    }).await;
+
+    connection.subscribe(&["media-player-state-changed"]).await.unwrap();
+    connection.wait_for(&Condition::media_playing(), Duration::from_secs(5)).await.unwrap();
+    assert_eq!(connection.next_notification().await.unwrap().event, "plugin-ui-run");
+    assert_eq!(connection.next_notification().await.unwrap().event, "media-player-state-changed");
+
+    drop(connection);
+    assert_eq!(server.await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn wait_for_queued_or_missing_notification() {
+    // The notification arrives ahead of a stale reply to the check
+    let mut polls = 0;
+    let (mut connection, _server) = ecp_stand_in(move |request| {
+        if request["request"] != "query-media-player" {
+            return vec![stand_in_reply(request, None)];
+        }
+        polls += 1;
+        if polls == 1 {
+            vec![
+                String::from(r#"{"notify":"media-player-state-changed","param-media-player-state":"play"}"#),
+                stand_in_reply(request, Some(r#"<player state="pause"/>"#)),
+            ]
+        }
+        else {
+            vec![stand_in_reply(request, Some(r#"<player state="play"/>"#))]
+        }
+    }).await;
+    connection.subscribe(&["media-player-state-changed"]).await.unwrap();
+    let started = Instant::now();
+    connection.wait_for(&Condition::media_playing(), Duration::from_secs(5)).await.unwrap();
+    assert!(started.elapsed() < Duration::from_millis(200));
+
+    // No notification is sent for this change, so polling finds the home screen
+    let mut polls = 0;
+    let (mut connection, _server) = ecp_stand_in(move |request| {
+        if request["request"] != "query-active-app" {
+            return vec![stand_in_reply(request, None)];
+        }
+        polls += 1;
+        let xml = if polls < 3 { r#"<active-app><app id="12">Netflix</app></active-app>"# }
+            else { "<active-app><app>Roku</app></active-app>" };
+        vec![stand_in_reply(request, Some(xml))]
+    }).await;
+    connection.subscribe(&[Condition::HomeScreen.event()]).await.unwrap();
+    let started = Instant::now();
+    connection.exit_to_home(Duration::from_secs(5)).await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn install_app_stand_in() {
+    let apps = |installed: bool| if installed {
+        r#"<apps><app id="12" type="appl" version="4.2.1">Netflix</app></apps>"#
+    } else {
+        "<apps/>"
+    };
+
+    // Installed once the install command has been sent
+    let mut installed = false;
+    let (mut connection, server) = ecp_stand_in(move |request| {
+        match request["request"].as_str() {
+            Some("install") => {
+                installed = true;
+                vec![stand_in_reply(request, None)]
+            }
+            _ => vec![stand_in_reply(request, Some(apps(installed)))],
+        }
+    }).await;
+    let report = connection.install_app(12, Duration::from_secs(5)).await.unwrap();
+    assert!(report.is_new());
+    assert!(!report.is_unchanged());
+    assert_eq!(connection.app_versions.get(&12).map(String::as_str), Some("4.2.1"));
+    drop(connection);
+    assert_eq!(server.await.unwrap().len(), 3);
+
+    // Already installed at the latest version
+    let (mut connection, _server) = ecp_stand_in(move |request| {
+        vec![stand_in_reply(request, Some(apps(true)))]
+    }).await;
+    let report = connection.install_app(12, Duration::from_millis(200)).await.unwrap();
+    assert!(report.is_unchanged());
+    assert_eq!(report.previous_version.as_deref(), Some("4.2.1"));
+
+    // Never appears
+    let (mut connection, _server) = ecp_stand_in(move |request| {
+        vec![stand_in_reply(request, Some(apps(false)))]
+    }).await;
+    assert_eq!(connection.install_app(12, Duration::from_millis(200)).await, Err(RequestError::TimedOut));
+}
+
+#[tokio::test]
+async fn exit_to_home_stand_in() {
+    let mut home = false;
+    let (mut connection, server) = ecp_stand_in(move |request| {
+        match request["request"].as_str() {
+            Some("key-press") => {
+                home = request["param-key"] == "Home";
+                vec![stand_in_reply(request, None)]
+            }
+            _ if home => vec![stand_in_reply(request, Some("<active-app><app>Roku</app></active-app>"))],
+            _ => vec![stand_in_reply(request, Some(r#"<active-app><app id="12">Netflix</app></active-app>"#))],
+        }
+    }).await;
+    connection.exit_to_home(Duration::from_secs(5)).await.unwrap();
+
+    drop(connection);
+    let requests = server.await.unwrap();
+    assert_eq!(requests[0]["param-key"], "Home");
+    assert_eq!(requests[1]["request"], "query-active-app");
+}
@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+
+use crate::media::{MediaPlayer, PlayerState};
+
+/// Position jumps larger than this beyond normal playback count as seeks
+const SEEK_THRESHOLD: Duration = Duration::from_secs(2);
+
+/// Change in playback observed by a `PlaybackTracker`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PlaybackEvent {
+    Started { time_to_first_frame: Duration },
+    BufferingStarted { position: Option<Duration> },
+    BufferingEnded { stalled: Duration },
+    Paused { position: Option<Duration> },
+    Resumed { position: Option<Duration> },
+    Seeked { from: Duration, to: Duration },
+    Ended { position: Option<Duration> },
+}
+
+/// Quality of experience statistics for the current playback session
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlaybackStats {
+    pub time_to_first_frame:    Option<Duration>,
+    pub play_time:              Duration,
+    pub rebuffer_time:          Duration,
+    pub rebuffer_count:         usize,
+    pub pause_count:            usize,
+    pub seek_count:             usize,
+}
+
+impl PlaybackStats {
+    /// Share of watch time spent rebuffering after playback started
+    pub fn rebuffer_ratio(&self) -> f64 {
+        let total = self.play_time + self.rebuffer_time;
+        if total.is_zero() {
+            0.0
+        }
+        else {
+            self.rebuffer_time.as_secs_f64() / total.as_secs_f64()
+        }
+    }
+}
+
+/// Follows media player snapshots and turns state changes into playback events
+#[derive(Clone, Debug, Default)]
+pub struct PlaybackTracker {
+    session_start:      Option<Instant>,
+    started:            bool,
+    ended:              bool,
+    last_at:            Option<Instant>,
+    last_state:         Option<PlayerState>,
+    last_position:      Option<Duration>,
+    buffering_since:    Option<Instant>,
+    stats:              PlaybackStats,
+}
+
+impl PlaybackTracker {
+    /// Create a tracker with no observations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Statistics for the current session
+    pub fn stats(&self) -> &PlaybackStats {
+        &self.stats
+    }
+
+    /// Record a media player snapshot taken at the given time and return any resulting events
+    pub fn update(&mut self, at: Instant, player: &MediaPlayer) -> Vec<PlaybackEvent> {
+        let mut events = vec![];
+        let state = &player.state;
+
+        // A new stream after the last one ended starts a new session
+        if self.ended && !state.is_stopped() {
+            *self = Self::default();
+        }
+
+        if !self.started {
+            if self.session_start.is_none() && !state.is_stopped() {
+                self.session_start = Some(at);
+            }
+            if *state == PlayerState::Play {
+                let time_to_first_frame = at.duration_since(self.session_start.unwrap_or(at));
+                self.started = true;
+                self.stats.time_to_first_frame = Some(time_to_first_frame);
+                events.push(PlaybackEvent::Started { time_to_first_frame });
+            }
+        }
+        else if !self.ended {
+            let elapsed = self.last_at.map(|last_at| at.duration_since(last_at)).unwrap_or_default();
+            let was_playing = self.last_state == Some(PlayerState::Play);
+            if was_playing {
+                self.stats.play_time += elapsed;
+            }
+
+            let was_buffering = self.last_state == Some(PlayerState::Buffer);
+            if !was_buffering && *state == PlayerState::Buffer {
+                self.buffering_since = Some(at);
+                self.stats.rebuffer_count += 1;
+                events.push(PlaybackEvent::BufferingStarted { position: player.position });
+            }
+            else if was_buffering && *state != PlayerState::Buffer {
+                let stalled = at.duration_since(self.buffering_since.take().unwrap_or(at));
+                self.stats.rebuffer_time += stalled;
+                events.push(PlaybackEvent::BufferingEnded { stalled });
+            }
+
+            if let (Some(from), Some(to)) = (self.last_position, player.position) {
+                let expected = if was_playing { from + elapsed } else { from };
+                let drift = to.abs_diff(expected);
+                if drift > SEEK_THRESHOLD {
+                    self.stats.seek_count += 1;
+                    events.push(PlaybackEvent::Seeked { from, to });
+                }
+            }
+
+            if *state == PlayerState::Pause && self.last_state != Some(PlayerState::Pause) {
+                self.stats.pause_count += 1;
+                events.push(PlaybackEvent::Paused { position: player.position });
+            }
+            else if self.last_state == Some(PlayerState::Pause) && *state == PlayerState::Play {
+                events.push(PlaybackEvent::Resumed { position: player.position });
+            }
+
+            if state.is_stopped() {
+                self.ended = true;
+                events.push(PlaybackEvent::Ended { position: player.position.or(self.last_position) });
+            }
+        }
+
+        self.last_at = Some(at);
+        self.last_state = Some(state.clone());
+        if player.position.is_some() {
+            self.last_position = player.position;
+        }
+        events
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+
+use crate::error::RequestError;
+
+/// TV input source
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum TvInput {
+    Tuner,
+    Hdmi1,
+    Hdmi2,
+    Hdmi3,
+    Hdmi4,
+    Av1,
+    Named(String),
+}
+
+impl TvInput {
+    /// Parse an input id such as "tvinput.hdmi1", or a plain name such as "HDMI1"
+    pub fn parse(id: &str) -> Self {
+        let normalized: String = id.trim()
+            .trim_start_matches("tvinput.")
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_lowercase();
+
+        match normalized.as_str() {
+            "dtv" | "tuner" | "tv" | "antenna" => TvInput::Tuner,
+            "hdmi1" => TvInput::Hdmi1,
+            "hdmi2" => TvInput::Hdmi2,
+            "hdmi3" => TvInput::Hdmi3,
+            "hdmi4" => TvInput::Hdmi4,
+            "cvbs" | "av" | "av1" => TvInput::Av1,
+            _ => TvInput::Named(String::from(id.trim())),
+        }
+    }
+
+    /// Parse the active input from the tv-active-input XML
+    pub fn parse_active(xml: &str) -> Result<Self, RequestError> {
+        let document = roxmltree::Document::parse(xml).map_err(|e| RequestError::Content(e.to_string()))?;
+        let element = document.descendants()
+            .find(|node| node.has_tag_name("input") || node.has_tag_name("active-input"))
+            .unwrap_or_else(|| document.root_element());
+
+        let id = element.attribute("id")
+            .or_else(|| element.children().find(|node| node.has_tag_name("id")).and_then(|node| node.text()))
+            .or_else(|| element.text())
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| RequestError::Content(String::from("missing active input")))?;
+        Ok(Self::parse(id))
+    }
+
+    /// Input id used to launch this input, such as "tvinput.hdmi1"
+    pub fn id(&self) -> String {
+        match self {
+            TvInput::Tuner => String::from("tvinput.dtv"),
+            TvInput::Hdmi1 => String::from("tvinput.hdmi1"),
+            TvInput::Hdmi2 => String::from("tvinput.hdmi2"),
+            TvInput::Hdmi3 => String::from("tvinput.hdmi3"),
+            TvInput::Hdmi4 => String::from("tvinput.hdmi4"),
+            TvInput::Av1 => String::from("tvinput.cvbs"),
+            TvInput::Named(id) => id.clone(),
+        }
+    }
+
+    /// Key which switches to this input, if it has one
+    pub fn key(&self) -> Option<&'static str> {
+        match self {
+            TvInput::Tuner => Some("InputTuner"),
+            TvInput::Hdmi1 => Some("InputHDMI1"),
+            TvInput::Hdmi2 => Some("InputHDMI2"),
+            TvInput::Hdmi3 => Some("InputHDMI3"),
+            TvInput::Hdmi4 => Some("InputHDMI4"),
+            TvInput::Av1 => Some("InputAV1"),
+            TvInput::Named(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for TvInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TvInput::Tuner => write!(f, "Tuner"),
+            TvInput::Hdmi1 => write!(f, "HDMI1"),
+            TvInput::Hdmi2 => write!(f, "HDMI2"),
+            TvInput::Hdmi3 => write!(f, "HDMI3"),
+            TvInput::Hdmi4 => write!(f, "HDMI4"),
+            TvInput::Av1 => write!(f, "AV1"),
+            TvInput::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Live TV channel from the tuner's channel list
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TvChannel {
+    pub number:         String,
+    pub name:           String,
+    pub channel_type:   String,
+    pub physical_id:    Option<String>,
+    pub hidden:         bool,
+    pub favorite:       bool,
+}
+
+impl TvChannel {
+    /// Parse the channel list from the tv-channels-ex XML
+    pub fn parse_list(xml: &str) -> Result<Vec<TvChannel>, RequestError> {
+        let document = roxmltree::Document::parse(xml).map_err(|e| RequestError::Content(e.to_string()))?;
+        Ok(document.descendants()
+            .filter(|node| node.has_tag_name("channel"))
+            .map(|node| Self::from_element(&node))
+            .collect())
+    }
+
+    /// Parse the channel from the tv-active-channel XML
+    pub fn parse_active(xml: &str) -> Result<TvChannel, RequestError> {
+        let document = roxmltree::Document::parse(xml).map_err(|e| RequestError::Content(e.to_string()))?;
+        document.descendants()
+            .find(|node| node.has_tag_name("channel"))
+            .map(|node| Self::from_element(&node))
+            .filter(|channel| !channel.number.is_empty())
+            .ok_or_else(|| RequestError::Content(String::from("no active channel")))
+    }
+
+    /// Read a channel element's fields
+    fn from_element(element: &roxmltree::Node) -> Self {
+        let text = |tag: &str| child_text(element, tag);
+        Self {
+            number: text("number").unwrap_or_default(),
+            name: text("name").unwrap_or_default(),
+            channel_type: text("type").unwrap_or_default(),
+            physical_id: text("physical-channel").or_else(|| text("physical-id")),
+            hidden: text("user-hidden").as_deref() == Some("true"),
+            favorite: text("user-favorite").as_deref() == Some("true"),
+        }
+    }
+}
+
+/// Trimmed, non-empty text of a child element
+fn child_text(element: &roxmltree::Node, tag: &str) -> Option<String> {
+    element.children()
+        .find(|node| node.has_tag_name(tag))
+        .and_then(|node| node.text())
+        .map(|text| String::from(text.trim()))
+        .filter(|text| !text.is_empty())
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use crate::error::RequestError;
+
+/// On-screen rectangle of a node
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub x:      f64,
+    pub y:      f64,
+    pub width:  f64,
+    pub height: f64,
+}
+
+impl Bounds {
+    /// Parse a bounds attribute such as "{0, 0, 1920, 1080}"
+    pub fn parse(text: &str) -> Option<Self> {
+        let values: Vec<f64> = text.trim_matches(|c| c == '{' || c == '}')
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .ok()?;
+
+        match values[..] {
+            [x, y, width, height] => Some(Self { x, y, width, height }),
+            _ => None,
+        }
+    }
+
+    /// Center point
+    pub fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+/// Node of the SceneGraph UI tree returned by the app-ui query
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct UiNode {
+    pub tag:        String,
+    pub attributes: HashMap<String, String>,
+    pub children:   Vec<UiNode>,
+}
+
+/// Criteria for finding UI nodes
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Selector {
+    Tag(String),
+    Attribute { name: String, value: String },
+    Text(String),
+    TextContains(String),
+    Focused,
+    All(Vec<Selector>),
+}
+
+impl Selector {
+    /// Node with the given name attribute
+    pub fn name(name: &str) -> Self {
+        Selector::Attribute { name: String::from("name"), value: String::from(name) }
+    }
+
+    /// Whether a node matches this selector
+    pub fn matches(&self, node: &UiNode) -> bool {
+        match self {
+            Selector::Tag(tag) => &node.tag == tag,
+            Selector::Attribute { name, value } => node.attribute(name) == Some(value.as_str()),
+            Selector::Text(text) => node.text() == Some(text.as_str()),
+            Selector::TextContains(text) => node.text().map(|own| own.contains(text.as_str())) == Some(true),
+            Selector::Focused => node.is_focused(),
+            Selector::All(selectors) => selectors.iter().all(|selector| selector.matches(node)),
+        }
+    }
+}
+
+impl UiNode {
+    /// Parse the app-ui XML into a tree rooted at its top element
+    pub fn parse(xml: &str) -> Result<Self, RequestError> {
+        let document = roxmltree::Document::parse(xml).map_err(|e| RequestError::Content(e.to_string()))?;
+        Ok(Self::from_element(document.root_element()))
+    }
+
+    /// Convert an XML element and its children
+    fn from_element(element: roxmltree::Node) -> Self {
+        Self {
+            tag: String::from(element.tag_name().name()),
+            attributes: element.attributes()
+                .map(|attribute| (String::from(attribute.name()), String::from(attribute.value())))
+                .collect(),
+            children: element.children()
+                .filter(|child| child.is_element())
+                .map(Self::from_element)
+                .collect(),
+        }
+    }
+
+    /// Get an attribute by name
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+
+    /// Displayed text, if any
+    pub fn text(&self) -> Option<&str> {
+        self.attribute("text")
+    }
+
+    /// Whether this node is marked as focused
+    pub fn is_focused(&self) -> bool {
+        self.attribute("focused") == Some("true")
+    }
+
+    /// Bounds of this node, if reported
+    pub fn bounds(&self) -> Option<Bounds> {
+        self.attribute("bounds").and_then(Bounds::parse)
+    }
+
+    /// All nodes in the tree, depth first
+    pub fn descendants(&self) -> Vec<&UiNode> {
+        let mut nodes = vec![self];
+        for child in &self.children {
+            nodes.append(&mut child.descendants());
+        }
+        nodes
+    }
+
+    /// First node matching a selector, depth first
+    pub fn find(&self, selector: &Selector) -> Option<&UiNode> {
+        self.descendants().into_iter().find(|node| selector.matches(node))
+    }
+
+    /// All nodes matching a selector
+    pub fn find_all(&self, selector: &Selector) -> Vec<&UiNode> {
+        self.descendants().into_iter().filter(|node| selector.matches(node)).collect()
+    }
+
+    /// Innermost focused node
+    pub fn focused(&self) -> Option<&UiNode> {
+        self.find_all(&Selector::Focused).into_iter().last()
+    }
+}
+
+/// Direction key which moves focus from one node toward another, based on their bounds
+pub fn direction_toward(from: &UiNode, to: &UiNode) -> Option<&'static str> {
+    let (from_x, from_y) = from.bounds()?.center();
+    let (to_x, to_y) = to.bounds()?.center();
+    let (dx, dy) = (to_x - from_x, to_y - from_y);
+
+    if dx == 0.0 && dy == 0.0 {
+        None
+    }
+    else if dx.abs() >= dy.abs() {
+        Some(if dx > 0.0 { "Right" } else { "Left" })
+    }
+    else {
+        Some(if dy > 0.0 { "Down" } else { "Up" })
+    }
+}
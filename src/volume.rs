@@ -0,0 +1,134 @@
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::connection::Connection;
+use crate::error::RequestError;
+
+/// Volume control through the VolumeUp, VolumeDown and VolumeMute keys, tracking an estimated level
+#[derive(Clone, Debug)]
+pub struct Volume {
+    pub max:            u32,
+    pub press_delay:    Duration,
+    level:              Option<u32>,
+    muted:              bool,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self { max: 100, press_delay: Duration::from_millis(100), level: None, muted: false }
+    }
+}
+
+impl Volume {
+    /// Create a controller with an unknown level
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a controller starting from a known level
+    pub fn with_level(level: u32) -> Self {
+        Self { level: Some(level), ..Self::default() }
+    }
+
+    /// Estimated level, if calibrated or reported by the device
+    pub fn level(&self) -> Option<u32> {
+        self.level
+    }
+
+    /// Whether the audio is muted, as far as this controller knows
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Key and number of presses needed to move from the current level to a target
+    pub fn presses_to(&self, target: u32) -> Option<(&'static str, u32)> {
+        let level = self.level?;
+        let target = target.min(self.max);
+        if target > level {
+            Some(("VolumeUp", target - level))
+        }
+        else {
+            Some(("VolumeDown", level - target))
+        }
+    }
+
+    /// Press a volume key a number of times
+    async fn press(&self, connection: &mut Connection, key: &str, times: u32) -> Result<(), RequestError> {
+        for _ in 0..times {
+            connection.press_key(key).await?;
+            sleep(self.press_delay).await;
+        }
+        Ok(())
+    }
+
+    /// Update the level from the audio settings, when the device reports one
+    pub async fn refresh(&mut self, connection: &mut Connection) -> Result<Option<u32>, RequestError> {
+        let settings = connection.audio_settings().await?;
+        let reported = settings.iter()
+            .find(|setting| setting.id == "volume")
+            .and_then(|setting| setting.value.parse::<u32>().ok());
+        if let Some(level) = reported {
+            self.level = Some(level.min(self.max));
+        }
+        Ok(self.level)
+    }
+
+    /// Press VolumeDown until the level must be zero
+    pub async fn calibrate(&mut self, connection: &mut Connection) -> Result<(), RequestError> {
+        self.press(connection, "VolumeDown", self.max).await?;
+        self.level = Some(0);
+        // Volume keys unmute the device
+        self.muted = false;
+        Ok(())
+    }
+
+    /// Move to a level, using the reported level if available and calibrating first if the level is unknown
+    pub async fn set_volume(&mut self, connection: &mut Connection, level: u32) -> Result<(), RequestError> {
+        // Many devices have no audio settings query, so a failed refresh keeps the tracked level
+        let _ = self.refresh(connection).await;
+        if self.level.is_none() {
+            self.calibrate(connection).await?;
+        }
+
+        if let Some((key, times)) = self.presses_to(level) {
+            self.press(connection, key, times).await?;
+            self.level = Some(level.min(self.max));
+            // Only a key press unmutes the device
+            if times > 0 {
+                self.muted = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Raise the volume one step
+    pub async fn volume_up(&mut self, connection: &mut Connection) -> Result<(), RequestError> {
+        connection.press_key("VolumeUp").await?;
+        self.level = self.level.map(|level| (level + 1).min(self.max));
+        self.muted = false;
+        Ok(())
+    }
+
+    /// Lower the volume one step
+    pub async fn volume_down(&mut self, connection: &mut Connection) -> Result<(), RequestError> {
+        connection.press_key("VolumeDown").await?;
+        self.level = self.level.map(|level| level.saturating_sub(1));
+        self.muted = false;
+        Ok(())
+    }
+
+    /// Toggle mute
+    pub async fn toggle_mute(&mut self, connection: &mut Connection) -> Result<(), RequestError> {
+        connection.press_key("VolumeMute").await?;
+        self.muted = !self.muted;
+        Ok(())
+    }
+
+    /// Mute or unmute, pressing VolumeMute only when the state differs
+    pub async fn set_muted(&mut self, connection: &mut Connection, muted: bool) -> Result<(), RequestError> {
+        if self.muted != muted {
+            self.toggle_mute(connection).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use serde_json::Value;
+
+use crate::protocol::query::Get;
+use crate::xml::{xml_attribute, xml_text};
+
+/// Device state to wait for with `Connection::wait_for`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Condition {
+    AppActive { app_id: String },
+    HomeScreen,
+    MediaState { state: String },
+    PowerOn,
+    TexteditVisible,
+}
+
+impl Condition {
+    /// Media player is playing
+    pub fn media_playing() -> Self {
+        Condition::MediaState { state: String::from("play") }
+    }
+
+    /// Query which reports the state this condition checks
+    pub fn query(&self) -> Get {
+        match self {
+            Condition::AppActive { .. } | Condition::HomeScreen => Get::ActiveApp,
+            Condition::MediaState { .. } => Get::MediaPlayer,
+            Condition::PowerOn => Get::DeviceInfo,
+            Condition::TexteditVisible => Get::TexteditState,
+        }
+    }
+
+    /// Notification event sent when the checked state may have changed
+    pub fn event(&self) -> &str {
+        match self {
+            Condition::AppActive { .. } => "plugin-ui-run",
+            Condition::HomeScreen => "plugin-ui-exit",
+            Condition::MediaState { .. } => "media-player-state-changed",
+            Condition::PowerOn => "power-mode-changed",
+            Condition::TexteditVisible => "textedit-opened",
+        }
+    }
+
+    /// Check the condition against the content of its query response
+    pub fn is_met(&self, content: &str) -> bool {
+        match self {
+            Condition::AppActive { app_id } => {
+                xml_attribute(content, "app", "id").as_deref() == Some(app_id.as_str())
+            }
+            Condition::HomeScreen => {
+                // The home screen is reported as an app without an id
+                xml_text(content, "app").is_some() && xml_attribute(content, "app", "id").is_none()
+            }
+            Condition::MediaState { state } => {
+                xml_attribute(content, "player", "state").as_deref() == Some(state.as_str())
+            }
+            Condition::PowerOn => {
+                xml_text(content, "power-mode").as_deref() == Some("PowerOn")
+            }
+            Condition::TexteditVisible => match textedit_id(content) {
+                Some(id) => !id.is_empty() && id != "none",
+                None => false,
+            },
+        }
+    }
+}
+
+/// Find the textedit id in either a JSON or XML textedit state
+fn textedit_id(content: &str) -> Option<String> {
+    match serde_json::from_str::<Value>(content) {
+        Ok(json) => {
+            let state = if json["textedit-state"].is_object() { &json["textedit-state"] } else { &json };
+            state["textedit-id"].as_str().map(String::from)
+        }
+        Err(_) => xml_text(content, "textedit-id"),
+    }
+}
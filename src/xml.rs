@@ -0,0 +1,22 @@
+/// Attribute of the first element with the given tag
+pub(crate) fn xml_attribute(xml: &str, tag: &str, attribute: &str) -> Option<String> {
+    let document = roxmltree::Document::parse(xml).ok()?;
+    let node = document.descendants().find(|node| node.has_tag_name(tag))?;
+    node.attribute(attribute).map(String::from)
+}
+
+/// Trimmed text of the first element with the given tag
+pub(crate) fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let document = roxmltree::Document::parse(xml).ok()?;
+    let node = document.descendants().find(|node| node.has_tag_name(tag))?;
+    node.text().map(|text| String::from(text.trim()))
+}
+
+/// Escape text for use in XML content or attribute values
+pub(crate) fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}